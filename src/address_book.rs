@@ -0,0 +1,178 @@
+//! Saved withdrawal destinations, kept in the browser's localStorage.
+use crate::{lnurl::Destination, storage};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const STORAGE_KEY: &str = "mercado-address-book";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedDestination {
+    pub label: String,
+    pub destination: String,
+    #[serde(default)]
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+pub fn load() -> Vec<SavedDestination> {
+    storage::load(STORAGE_KEY).unwrap_or_default()
+}
+pub fn save(book: &[SavedDestination]) {
+    storage::save(STORAGE_KEY, &book)
+}
+/// Adds a destination or relabels it if it's already saved.
+pub fn add(
+    book: &mut Vec<SavedDestination>,
+    label: String,
+    destination: String,
+) -> Result<(), String> {
+    let destination = destination.trim().to_string();
+    // Invoices can only be paid once, so they aren't worth saving.
+    if let Destination::Invoice(_) = Destination::parse(destination.as_str())? {
+        return Err("Only Lightning Addresses and LNURLs can be saved".to_string());
+    }
+    if label.trim().is_empty() {
+        return Err("Give the destination a label".to_string());
+    }
+    if let Some(saved) = book
+        .iter_mut()
+        .find(|saved| saved.destination == destination)
+    {
+        saved.label = label;
+    } else {
+        book.push(SavedDestination {
+            label,
+            destination,
+            last_used: None,
+        });
+    }
+    Ok(())
+}
+pub fn mark_used(book: &mut [SavedDestination], destination: &str) {
+    if let Some(saved) = book
+        .iter_mut()
+        .find(|saved| saved.destination == destination)
+    {
+        saved.last_used = Some(Utc::now());
+    }
+}
+/// Most recently used first, never used ones by label.
+pub fn sorted(mut book: Vec<SavedDestination>) -> Vec<SavedDestination> {
+    book.sort_by(|a, b| {
+        b.last_used
+            .cmp(&a.last_used)
+            .then_with(|| a.label.cmp(&b.label))
+    });
+    book
+}
+pub fn to_json(book: &[SavedDestination]) -> Result<String, String> {
+    serde_json::to_string_pretty(book).map_err(|e| e.to_string())
+}
+/// Merges an exported address book and returns how many entries it contained.
+/// Nothing is merged if any entry is invalid.
+pub fn import(book: &mut Vec<SavedDestination>, json: &str) -> Result<usize, String> {
+    let imported =
+        serde_json::from_str::<Vec<SavedDestination>>(json).map_err(|e| e.to_string())?;
+    let count = imported.len();
+    let mut merged = book.clone();
+    for saved in imported {
+        let destination = saved.destination.trim().to_string();
+        add(&mut merged, saved.label, destination.clone())
+            .map_err(|e| format!("{}: {}", destination, e))?;
+        if let Some(existing) = merged
+            .iter_mut()
+            .find(|existing| existing.destination == destination)
+        {
+            existing.last_used = existing.last_used.max(saved.last_used);
+        }
+    }
+    *book = merged;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn saved(label: &str, destination: &str, last_used: Option<DateTime<Utc>>) -> SavedDestination {
+        SavedDestination {
+            label: label.to_string(),
+            destination: destination.to_string(),
+            last_used,
+        }
+    }
+
+    #[test]
+    fn adds_and_relabels_destinations() {
+        let mut book = vec![];
+        add(
+            &mut book,
+            "Alice".to_string(),
+            " alice@example.com ".to_string(),
+        )
+        .unwrap();
+        add(
+            &mut book,
+            "Alice's wallet".to_string(),
+            "alice@example.com".to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            book,
+            vec![saved("Alice's wallet", "alice@example.com", None)]
+        );
+    }
+    #[test]
+    fn rejects_invoices_and_missing_labels() {
+        let mut book = vec![];
+        assert!(add(&mut book, "Once".to_string(), "lnbc10u1abc".to_string()).is_err());
+        assert!(add(&mut book, " ".to_string(), "alice@example.com".to_string()).is_err());
+        assert!(add(&mut book, "Nothing".to_string(), "alice".to_string()).is_err());
+        assert!(book.is_empty());
+    }
+    #[test]
+    fn sorts_recently_used_first_then_by_label() {
+        let now = Utc::now();
+        let book = sorted(vec![
+            saved("b", "b@example.com", None),
+            saved("old", "old@example.com", Some(now - Duration::days(1))),
+            saved("a", "a@example.com", None),
+            saved("new", "new@example.com", Some(now)),
+        ]);
+        let labels = book
+            .iter()
+            .map(|saved| saved.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["new", "old", "a", "b"]);
+    }
+    #[test]
+    fn imports_and_keeps_the_latest_use() {
+        let now = Utc::now();
+        let mut book = vec![saved("Alice", "alice@example.com", Some(now))];
+        let exported = to_json(&[
+            saved("Alice", "alice@example.com", Some(now - Duration::days(1))),
+            saved("Bob", "bob@example.com", None),
+        ])
+        .unwrap();
+        assert_eq!(import(&mut book, exported.as_str()), Ok(2));
+        assert_eq!(
+            book,
+            vec![
+                saved("Alice", "alice@example.com", Some(now)),
+                saved("Bob", "bob@example.com", None),
+            ]
+        );
+    }
+    #[test]
+    fn import_is_all_or_nothing() {
+        let mut book = vec![saved("Alice", "alice@example.com", None)];
+        let exported = to_json(&[
+            saved("Bob", "bob@example.com", None),
+            saved("Invoice", "lnbc10u1abc", None),
+        ])
+        .unwrap();
+        assert!(import(&mut book, exported.as_str()).is_err());
+        assert!(import(&mut book, "not json").is_err());
+        assert_eq!(book, vec![saved("Alice", "alice@example.com", None)]);
+    }
+}
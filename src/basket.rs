@@ -0,0 +1,43 @@
+//! Bets collected across predictions to be reviewed and placed together. Kept
+//! in the browser's localStorage so they survive reloads.
+use crate::storage;
+use chrono::Utc;
+use mercado::api::{RowId, Sats};
+use serde::{Deserialize, Serialize};
+
+const STORAGE_KEY: &str = "mercado-bet-basket";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BasketItem {
+    pub id: i64,
+    pub prediction: RowId,
+    pub name: String,
+    pub bet: bool,
+    pub amount: Sats,
+}
+
+pub fn load() -> Vec<BasketItem> {
+    storage::load(STORAGE_KEY).unwrap_or_default()
+}
+pub fn save(items: &[BasketItem]) {
+    storage::save(STORAGE_KEY, &items)
+}
+/// Adds a bet. Bets on the same side of a prediction are combined.
+pub fn add(prediction: RowId, name: String, bet: bool, amount: Sats) {
+    let mut items = load();
+    if let Some(item) = items
+        .iter_mut()
+        .find(|item| item.prediction == prediction && item.bet == bet)
+    {
+        item.amount += amount;
+    } else {
+        items.push(BasketItem {
+            id: Utc::now().timestamp_millis(),
+            prediction,
+            name,
+            bet,
+            amount,
+        });
+    }
+    save(&items)
+}
@@ -0,0 +1,172 @@
+//! Geometry for the SVG charts. Everything here is plain Rust so the chart
+//! components only have to place the computed coordinates.
+use chrono::{DateTime, Utc};
+
+pub const WIDTH: f64 = 600.0;
+pub const HEIGHT: f64 = 240.0;
+pub const PADDING: f64 = 40.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub label: String,
+    pub color: &'static str,
+    pub points: Vec<(DateTime<Utc>, i64)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub min: i64,
+    pub max: i64,
+}
+impl Scale {
+    /// Fits all points of all series. Returns `None` if there are no points.
+    pub fn fit(series: &[Series]) -> Option<Self> {
+        let points = series.iter().flat_map(|series| series.points.iter());
+        let mut scale: Option<Self> = None;
+        for (time, value) in points {
+            scale = Some(match scale {
+                None => Self {
+                    start: *time,
+                    end: *time,
+                    min: (*value).min(0),
+                    max: *value,
+                },
+                Some(scale) => Self {
+                    start: scale.start.min(*time),
+                    end: scale.end.max(*time),
+                    min: scale.min.min(*value),
+                    max: scale.max.max(*value),
+                },
+            });
+        }
+        scale
+    }
+    pub fn x(&self, time: DateTime<Utc>) -> f64 {
+        let span = (self.end - self.start).num_seconds().max(1) as f64;
+        let offset = (time - self.start).num_seconds() as f64;
+        PADDING + offset / span * (WIDTH - 2.0 * PADDING)
+    }
+    pub fn y(&self, value: i64) -> f64 {
+        let span = (self.max - self.min).max(1) as f64;
+        HEIGHT - PADDING - (value - self.min) as f64 / span * (HEIGHT - 2.0 * PADDING)
+    }
+    /// SVG `points` attribute for a step line, since balances change in jumps.
+    pub fn step_line(&self, points: &[(DateTime<Utc>, i64)]) -> String {
+        let mut line = vec![];
+        let mut previous: Option<i64> = None;
+        for (time, value) in points {
+            if let Some(previous) = previous {
+                line.push(format!("{:.1},{:.1}", self.x(*time), self.y(previous)));
+            }
+            line.push(format!("{:.1},{:.1}", self.x(*time), self.y(*value)));
+            previous = Some(*value);
+        }
+        line.join(" ")
+    }
+}
+
+/// Drops points before `since` but keeps the value that was current at that
+/// time as the new first point.
+pub fn since(points: &[(DateTime<Utc>, i64)], since: DateTime<Utc>) -> Vec<(DateTime<Utc>, i64)> {
+    let before = points.iter().filter(|(time, _)| *time < since).last();
+    let after = points.iter().filter(|(time, _)| *time >= since).cloned();
+    before
+        .map(|(_, value)| (since, *value))
+        .into_iter()
+        .chain(after)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::seconds(seconds)
+    }
+    fn series(points: Vec<(DateTime<Utc>, i64)>) -> Series {
+        Series {
+            label: "Balance".to_string(),
+            color: "red",
+            points,
+        }
+    }
+    fn scale() -> Scale {
+        Scale {
+            start: at(0),
+            end: at(100),
+            min: 0,
+            max: 100,
+        }
+    }
+
+    #[test]
+    fn fits_all_series_and_includes_zero() {
+        assert_eq!(Scale::fit(&[]), None);
+        assert_eq!(Scale::fit(&[series(vec![])]), None);
+        assert_eq!(
+            Scale::fit(&[series(vec![(at(10), 50)])]),
+            Some(Scale {
+                start: at(10),
+                end: at(10),
+                min: 0,
+                max: 50,
+            })
+        );
+        let fitted = Scale::fit(&[
+            series(vec![(at(50), 100), (at(100), 30)]),
+            series(vec![(at(0), -20)]),
+        ]);
+        assert_eq!(
+            fitted,
+            Some(Scale {
+                start: at(0),
+                end: at(100),
+                min: -20,
+                max: 100,
+            })
+        );
+    }
+    #[test]
+    fn maps_into_the_padded_area() {
+        let scale = scale();
+        assert_eq!(scale.x(at(0)), PADDING);
+        assert_eq!(scale.x(at(50)), WIDTH / 2.0);
+        assert_eq!(scale.x(at(100)), WIDTH - PADDING);
+        assert_eq!(scale.y(0), HEIGHT - PADDING);
+        assert_eq!(scale.y(50), HEIGHT / 2.0);
+        assert_eq!(scale.y(100), PADDING);
+    }
+    #[test]
+    fn handles_a_single_point() {
+        let scale = Scale {
+            start: at(0),
+            end: at(0),
+            min: 5,
+            max: 5,
+        };
+        assert_eq!(scale.x(at(0)), PADDING);
+        assert_eq!(scale.y(5), HEIGHT - PADDING);
+    }
+    #[test]
+    fn draws_steps() {
+        let scale = scale();
+        assert_eq!(scale.step_line(&[]), "");
+        assert_eq!(scale.step_line(&[(at(0), 0)]), "40.0,200.0");
+        assert_eq!(
+            scale.step_line(&[(at(0), 0), (at(50), 100), (at(100), 50)]),
+            "40.0,200.0 300.0,200.0 300.0,40.0 560.0,40.0 560.0,120.0"
+        );
+    }
+    #[test]
+    fn keeps_the_value_current_at_the_cutoff() {
+        let points = vec![(at(0), 1), (at(10), 2), (at(20), 3)];
+        assert_eq!(since(&points, at(15)), vec![(at(15), 2), (at(20), 3)]);
+        assert_eq!(since(&points, at(-5)), points);
+        assert_eq!(since(&points, at(30)), vec![(at(30), 3)]);
+        assert!(since(&[], at(0)).is_empty());
+    }
+}
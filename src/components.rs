@@ -1,20 +1,31 @@
 #![allow(non_snake_case)]
-use crate::{fetchers::*, MercadoState};
+use crate::{
+    address_book::{self, SavedDestination},
+    basket::{self, BasketItem},
+    chart::{self, Series},
+    created,
+    drafts::{self, Draft},
+    export::{self, export_history, ExportFormat, ExportKind},
+    fetchers::*,
+    limits::{Verdict, WithdrawalLimits},
+    lnurl,
+    price::PriceFeed,
+    sats::{self, Unit},
+    validation::{self, Field},
+    webln, MercadoState,
+};
 use anyhow::{bail, Context};
-use chrono::DateTime;
 use chrono::{offset::Utc, Duration};
+use chrono::{DateTime, NaiveDate, NaiveTime};
 use leptonic::prelude::*;
 use leptos::{html::Input, *};
 use leptos_router::*;
-use mercado::{
-    api::*,
-    secp256k1::{generate_keypair, rand},
-};
+use mercado::api::*;
 
 #[component]
-pub fn App() -> impl IntoView {
+pub fn App(state: ReadSignal<MercadoState>) -> impl IntoView {
     view! {
-        <PredictionList/>
+        <PredictionList state=state />
     }
 }
 #[component]
@@ -35,8 +46,15 @@ pub fn Navi(
             <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(1.0) style="padding-right: 20px">
                 <Link href="/">"Predictions"</Link>
                 <Link href="/my_bets">"Bets"</Link>
+                <Link href="/basket">"Basket"</Link>
                 <Link href="/my_judges">"Judges"</Link>
-                <Link href="#">"Users"</Link>
+                {move || {
+                    if let Some(UserRole::Root | UserRole::Admin) = state.get().user.map(|user| user.role) {
+                        view! {<Link href="/users">"Users"</Link>}.into_view()
+                    } else {
+                        view! {}.into_view()
+                    }
+                }}
                 {move || {
                     let access = if let Some(access) = state.get().access {
                         access
@@ -46,7 +64,9 @@ pub fn Navi(
                     let balances = create_local_resource(move || {}, move |_| get_balances_for(access.clone()));
                     view!{
                         <UnwrapResourceFor state=state resource=balances view=move |balances| { view! {
-                            <Link href="/wallet">{balances.0}"/"{balances.1}" sats"</Link>
+                            <span title="available/total, see the wallet for what locks the difference">
+                                <Link href="/wallet"><SatsDisplay amount=balances.0 state=state no_fiat=true />"/"<SatsDisplay amount=balances.1 state=state no_fiat=true /></Link>
+                            </span>
                         }} />
                     }.into_view()
                 }}
@@ -60,7 +80,9 @@ pub fn Navi(
                                     None
                                 }
                             } no_clipboard=true />
-                            <LinkButton href="/" on:click=move |_| {set_state.set(MercadoState::default())} >"Logout"</LinkButton>
+                            <LinkButton href="/" on:click=move |_| {
+                                set_state.update(|state| *state = MercadoState {access: None, user: None, ..state.clone()})
+                            } >"Logout"</LinkButton>
                         }.into_view()
                     } else {
                         view!{
@@ -68,12 +90,108 @@ pub fn Navi(
                         }.into_view()
                     }
                 }
+            <select title="Unit for amounts" on:change=move |ev| {
+                if let Ok(unit) = event_target_value(&ev).parse::<Unit>() {
+                    set_state.update(|state| state.unit = unit);
+                }
+            }>
+                {Unit::ALL.into_iter().map(|unit| view!{
+                    <option value=unit.to_string() selected=move || state.get().unit == unit>{unit.to_string()}</option>
+                }).collect::<Vec<_>>()}
+            </select>
+            <Link href="/settings">"Settings"</Link>
             <ThemeToggle off=LeptonicTheme::Light on=LeptonicTheme::Dark/>
             </Stack>
         </AppBar>
     }
 }
 #[component]
+pub fn WalletBanner(state: ReadSignal<MercadoState>) -> impl IntoView {
+    let location = use_location();
+    move || {
+        let path = location.pathname.get();
+        let mut segments = path.trim_matches('/').split('/');
+        let operated = match (segments.next(), segments.next()) {
+            (Some("wallet" | "make_deposit" | "make_withdrawal"), Some(user)) => {
+                user.parse::<UserPubKey>().ok()
+            }
+            _ => None,
+        };
+        match (operated, state.get().access) {
+            (Some(user), Some(access)) if user != access.user => view! {
+                <div class="wallet-banner">
+                    "You are operating on the wallet of "<Username user=Some(user) />" "
+                    <Link href=format!("/wallet/{}", access.user)>"Back to my wallet"</Link>
+                </div>
+            }
+            .into_view(),
+            _ => view! {}.into_view(),
+        }
+    }
+}
+#[component]
+pub fn Users(state: ReadSignal<MercadoState>) -> impl IntoView {
+    let access = if let Some(access) = state.get_untracked().access {
+        access
+    } else {
+        return view! {}.into_view();
+    };
+    if let Some(UserRole::User) | None = state.get_untracked().user.map(|user| user.role) {
+        return view! {<p>"Only admins can manage the wallets of other users"</p>}.into_view();
+    }
+    let users = create_local_resource(move || {}, move |_| get_known_users(access.clone()));
+    let (search, set_search) = create_signal(String::new());
+
+    view! {
+        <Stack spacing=Size::Em(1.0)>
+            <H3>"Users"</H3>
+            <Box style="width: 50%">
+                <TextInput get=search set=set_search placeholder="Search username or paste public key" />
+            </Box>
+            {move || search.get().trim().parse::<UserPubKey>().ok().map(|user| view!{
+                <p>"Public key: "<Username user=Some(user) />" "<UserWalletLinks user=user /></p>
+            })}
+            <UnwrapResource resource=users view=move |users| view!{
+                <TableContainer><Table bordered=true hoverable=true>
+                    <Thead><Tr>
+                        <Th>"User"</Th>
+                        <Th>"Actions"</Th>
+                    </Tr></Thead>
+                    <Tbody>{
+                        let users = users.clone();
+                        move || {
+                            let search = search.get().to_lowercase();
+                            users.iter()
+                                .filter(|(user, name)| {
+                                    name.to_lowercase().contains(search.as_str())
+                                        || user.to_string().contains(search.as_str())
+                                })
+                                .map(|(user, _)| view!{
+                                    <Tr>
+                                        <Td><Username user=Some(*user) /></Td>
+                                        <Td><UserWalletLinks user=*user /></Td>
+                                    </Tr>
+                                })
+                                .collect::<Vec<_>>()
+                        }
+                    }</Tbody>
+                </Table></TableContainer>
+            } />
+        </Stack>
+    }
+    .into_view()
+}
+#[component]
+pub fn UserWalletLinks(user: UserPubKey) -> impl IntoView {
+    view! {
+        <ButtonGroup>
+            <LinkButton href=format!("/wallet/{}", user)>"Wallet"</LinkButton>
+            <LinkButton href=format!("/make_deposit/{}", user)>"Deposit"</LinkButton>
+            <LinkButton href=format!("/make_withdrawal/{}", user)>"Withdrawal"</LinkButton>
+        </ButtonGroup>
+    }
+}
+#[component]
 pub fn Login(set_state: WriteSignal<MercadoState>) -> impl IntoView {
     let (user, set_user) = create_signal(String::from(""));
     let challenge = create_local_resource(move || user.get(), create_login_challenge);
@@ -127,12 +245,14 @@ pub fn Login(set_state: WriteSignal<MercadoState>) -> impl IntoView {
 pub fn PredictionListItem(
     prediction: PredictionOverviewResponse,
     refresh: RwSignal<bool>,
+    placed: RwSignal<Option<PlacedBet>>,
+    state: ReadSignal<MercadoState>,
 ) -> impl IntoView {
     let ratio = prediction.ratio;
     view! {
         <Box style="width: 100%"><Separator /></Box>
         <Stack orientation=StackOrientation::Horizontal spacing=Size::Zero style="width:100%">
-            <Box style="width:55%"><Link href={format!("/prediction/{}", prediction.id)}>{prediction.name}</Link></Box>
+            <Box style="width:55%"><Link href={format!("/prediction/{}", prediction.id)}>{prediction.name.clone()}</Link></Box>
             <Box style="width:45%"><ProgressBar progress=Some(ratio.0 as f64) max={(ratio.0+ratio.1) as f64} /></Box>
         </Stack>
         <Stack orientation=StackOrientation::Horizontal spacing=Size::Zero style="width:100%">
@@ -140,31 +260,195 @@ pub fn PredictionListItem(
             <Box style="width:15%">{prediction.judge_share_ppm / 10000}"%"</Box>
             <Box style="width:20%">{prediction.state.to_string()}</Box>
             <Box style="width:20%">
-                <span>{format!("True: {}% ({} sats)",
+                <span>{format!("True: {}% (",
                      ratio.0 as f32/(ratio.0+ratio.1)as f32*100.0,
-                     ratio.0,
-                )}</span>
+                )}<SatsDisplay amount=ratio.0 state=state no_fiat=true />")"</span>
             </Box>
             <Box style="width:10%">
-                <span>{format!("Total: {} sats)",
-                    ratio.0+ratio.1
-                )}</span>
+                <span>"Total: "<SatsDisplay amount={ratio.0+ratio.1} state=state no_fiat=true /></span>
             </Box>
             <Box style="width:15%">
-                <span style="float:right">{format!("False: {}% ({} sats)",
+                <span style="float:right">{format!("False: {}% (",
                      ratio.1 as f32/(ratio.0+ratio.1)as f32*100.0,
-                     ratio.1,
-                )}</span>
+                )}<SatsDisplay amount=ratio.1 state=state no_fiat=true />")"</span>
             </Box>
         </Stack>
+        <Cond cond={prediction.state == MarketState::Trading} view=view!{
+            <QuickBet prediction=prediction.clone() state=state refresh=refresh placed=placed />
+        } />
     }
 }
+/// Preset stakes offered in the bet slip.
+const BET_PRESETS: [Sats; 4] = [1_000, 10_000, 50_000, 100_000];
 #[component]
-pub fn PredictionList() -> impl IntoView {
-    let predictions = create_local_resource(move || {}, get_predictions);
+pub fn QuickBet(
+    prediction: PredictionOverviewResponse,
+    state: ReadSignal<MercadoState>,
+    refresh: RwSignal<bool>,
+    placed: RwSignal<Option<PlacedBet>>,
+) -> impl IntoView {
+    let side = create_rw_signal(None::<bool>);
+    view! {
+        <ButtonGroup>
+            <Button on_click=move |_| side.set(Some(true))>"Yes"</Button>
+            <Button on_click=move |_| side.set(Some(false))>"No"</Button>
+        </ButtonGroup>
+        {move || side.get().map(|bet| view!{
+            <BetSlip prediction=prediction.clone() bet=bet state=state refresh=refresh placed=placed on_close=move || side.set(None) />
+        })}
+    }
+}
+#[component]
+pub fn BetSlip<F>(
+    prediction: PredictionOverviewResponse,
+    bet: bool,
+    state: ReadSignal<MercadoState>,
+    refresh: RwSignal<bool>,
+    /// Where the placed bet is reported. Placing a bet refreshes the page the
+    /// slip lives in, so the slip closes and can't show it itself.
+    placed: RwSignal<Option<PlacedBet>>,
+    on_close: F,
+) -> impl IntoView
+where
+    F: Fn() + Copy + 'static,
+{
+    let access = if let Some(access) = state.get_untracked().access {
+        access
+    } else {
+        return view! {
+            <p><Link href="/login">"Login"</Link>" to bet" <Button on_click=move |_| on_close()>"Close"</Button></p>
+        }
+        .into_view();
+    };
+    let (id, user) = (prediction.id, access.user);
+    let name = prediction.name.clone();
+    let amount = create_rw_signal::<Sats>(BET_PRESETS[0]);
+    let amount_valid = create_rw_signal(true);
+    // Stakes above the balance go through the bet page, which offers to
+    // deposit the difference.
+    let available = create_local_resource(move || refresh.get(), {
+        let access = access.clone();
+        move |_| get_available_balance(user, access.clone())
+    });
+    let short = move || {
+        available
+            .get()
+            .and_then(|available| available.ok())
+            .is_some_and(|available| amount.get() > available)
+    };
+    let place = create_action(move |amount: &Sats| {
+        let request = AddBetRequest {
+            prediction: id,
+            bet,
+            user,
+            amount: *amount,
+        };
+        add_bet(request, access.clone())
+    });
+    let placed_name = name.clone();
+    create_effect(move |_| {
+        if let Some(Ok(payment)) = place.value().get() {
+            placed.set(Some(PlacedBet {
+                name: placed_name.clone(),
+                bet,
+                invoice: payment.to_string(),
+            }));
+            on_close();
+            refresh.set(!refresh.get_untracked());
+        }
+    });
+    let ratio = prediction.ratio;
+    let judge_share_ppm = prediction.judge_share_ppm;
+    let projected = move || {
+        let amount = amount.get();
+        let ratio = if bet {
+            (ratio.0 + amount, ratio.1)
+        } else {
+            (ratio.0, ratio.1 + amount)
+        };
+        payout(amount, bet, ratio, judge_share_ppm)
+    };
+
+    view! {
+        <div class="bet-slip">
+            <strong>{format!("Bet {} on ", bet)}{prediction.name.clone()}</strong>
+            <ButtonGroup>{BET_PRESETS.into_iter().map(|preset| view!{
+                <Button on_click=move |_| amount.set(preset)>{sats::format(preset, state.get_untracked().unit)}</Button>
+            }).collect::<Vec<_>>()}</ButtonGroup>
+            <SatsInput value=amount valid=amount_valid state=state min=1 />
+            <p>"Pays "{move || view!{<SatsDisplay amount=projected() state=state />}}" if "{bet.to_string()}" wins at current ratios"</p>
+            {move || place.value().get().and_then(|result| result.err()).map(|e| view!{
+                <small>{format!("{:?}", e)}</small>
+            })}
+            <ButtonGroup>
+                {move || if short() {
+                    view!{
+                        <LinkButton href=format!("/add_bet?prediction={}&bet={}&amount={}", id, bet, amount.get())>
+                            "Deposit the difference and bet"
+                        </LinkButton>
+                    }.into_view()
+                } else {
+                    view!{
+                        <Button
+                            disabled=Signal::derive(move || !amount_valid.get() || place.pending().get())
+                            on_click=move |_| place.dispatch(amount.get_untracked())
+                        >"Place bet"</Button>
+                    }.into_view()
+                }}
+                <Button
+                    disabled=Signal::derive(move || !amount_valid.get())
+                    on_click=move |_| {
+                        basket::add(id, name.clone(), bet, amount.get_untracked());
+                        on_close();
+                    }
+                >"Add to basket"</Button>
+                <Button on_click=move |_| on_close()>"Close"</Button>
+            </ButtonGroup>
+        </div>
+    }
+    .into_view()
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedBet {
+    pub name: String,
+    pub bet: bool,
+    pub invoice: Invoice,
+}
+/// The invoice `add_bet` returns. The bet only counts once it's paid.
+#[component]
+pub fn BetPayment(invoice: Invoice, state: ReadSignal<MercadoState>) -> impl IntoView {
+    view! {
+        <small>"Pay this invoice to confirm the bet: "</small>
+        <small style="word-break: break-all">{invoice.clone()}</small><br/>
+        <WeblnPay invoice=invoice tx=None state=state />
+    }
+}
+/// The bet last placed from a bet slip, with its invoice to pay.
+#[component]
+pub fn PlacedBetNotice(
+    placed: RwSignal<Option<PlacedBet>>,
+    state: ReadSignal<MercadoState>,
+) -> impl IntoView {
+    move || {
+        placed.get().map(|placed_bet| {
+            view! {
+                <div class="bet-slip">
+                    <strong>{format!("Bet {} placed on ", placed_bet.bet)}{placed_bet.name}</strong><br/>
+                    <BetPayment invoice=placed_bet.invoice state=state />
+                    <Button on_click=move |_| placed.set(None)>"Dismiss"</Button>
+                </div>
+            }
+        })
+    }
+}
+#[component]
+pub fn PredictionList(state: ReadSignal<MercadoState>) -> impl IntoView {
     let refresh = create_rw_signal(true);
+    let predictions = create_local_resource(move || refresh.get(), |_| get_predictions(()));
+    let placed = create_rw_signal(None);
 
     view! {
+        <PlacedBetNotice placed=placed state=state />
         <UnwrapResource resource=predictions view=move |mut predictions| view! {
             <p>{predictions.len()}" prediction(s)"
                 <span style="float:right">
@@ -182,7 +466,7 @@ pub fn PredictionList() -> impl IntoView {
                     predictions.sort_by(|a, b| (a.ratio.0+a.ratio.1).cmp(&(b.ratio.0+b.ratio.1)));
                     predictions.reverse();
                     predictions.into_iter()
-                    .map(|prediction| view! {<PredictionListItem prediction=prediction refresh=refresh/>})
+                    .map(|prediction| view! {<PredictionListItem prediction=prediction refresh=refresh placed=placed state=state />})
                     .collect::<Vec<_>>()
                 }
             </Stack>
@@ -296,7 +580,9 @@ pub fn PredictionOverview(state: ReadSignal<MercadoState>) -> impl IntoView {
     } else {
         None
     };
+    let placed = create_rw_signal(None);
     view! {
+        <PlacedBetNotice placed=placed state=state />
         <UnwrapResource resource=prediction view=move |prediction| view! {
             <h3>{prediction.name.clone()}</h3>
             {
@@ -331,16 +617,14 @@ pub fn PredictionOverview(state: ReadSignal<MercadoState>) -> impl IntoView {
             </p>
             <p>
                 <UnwrapResource resource=ratio view=move |ratio| view! {
-                    <span>{format!("True: {}% ({} sats)",
+                    <span>{format!("True: {}% (",
                          ratio.0 as f32/(ratio.0+ratio.1)as f32*100.0,
-                         ratio.0,
-                    )}</span>
-                    <span style="float:right">{format!("False: {}% ({} sats)",
+                    )}<SatsDisplay amount=ratio.0 state=state />")"</span>
+                    <span style="float:right">{format!("False: {}% (",
                          ratio.1 as f32/(ratio.0+ratio.1)as f32*100.0,
-                         ratio.1,
-                    )}</span><br/>
+                    )}<SatsDisplay amount=ratio.1 state=state />")"</span><br/>
                     <ProgressBar progress={Some(ratio.0 as f64)} max={(ratio.0+ratio.1) as f64}/>
-                    <p style="text-align:center">"Total: "{ratio.0+ratio.1}" sats"</p>
+                    <p style="text-align:center">"Total: "<SatsDisplay amount={ratio.0+ratio.1} state=state /></p>
                 } />
                 <Button on_click=move |_| {
                     refresh.set(!refresh.get());
@@ -348,8 +632,16 @@ pub fn PredictionOverview(state: ReadSignal<MercadoState>) -> impl IntoView {
                 <Cond cond={prediction.state == MarketState::Trading} view=view!{
                     <LinkButton href={move || format!("/add_bet?prediction={}", prediction.id)} >
                         "Add bet"</LinkButton>
+                    <QuickBet prediction=prediction.clone() state=state refresh=refresh placed=placed />
                 } />
+                <LinkButton href=format!("/new_prediction?duplicate={}", prediction.id)>
+                    "Duplicate this prediction"</LinkButton>
             </p>
+            <MyPosition prediction=prediction.clone() state=state refresh=refresh />
+            <JudgeConsensus prediction=prediction.clone() state=state refresh=refresh />
+            <Cond cond={created::is_mine(prediction.id) || state.get_untracked().user.is_some_and(|user| user.role != UserRole::User)} view=view!{
+                <CreatorPanel prediction=prediction.clone() state=state refresh=refresh />
+            } />
             <JudgeList prediction=Some(prediction.id) user=user state=state refresh=refresh collapsable=true/>
             <BetList prediction=Some(prediction.id) state=state collapsable=true
                 user=user
@@ -359,6 +651,226 @@ pub fn PredictionOverview(state: ReadSignal<MercadoState>) -> impl IntoView {
     }
 }
 #[component]
+pub fn MyPosition(
+    prediction: PredictionOverviewResponse,
+    state: ReadSignal<MercadoState>,
+    refresh: RwSignal<bool>,
+) -> impl IntoView {
+    let access = if let Some(access) = state.get_untracked().access {
+        create_rw_signal(access)
+    } else {
+        return view! {}.into_view();
+    };
+    let id = prediction.id;
+    let (ratio, judge_share_ppm) = (prediction.ratio, prediction.judge_share_ppm);
+    let trading = prediction.state == MarketState::Trading;
+    let outcome = match prediction.state {
+        MarketState::Resolved(outcome) => Some(outcome),
+        _ => None,
+    };
+    let position = create_local_resource(
+        move || (access.get(), refresh.get()),
+        move |(access, _)| get_position(id, access),
+    );
+    let cancel =
+        create_action(|(id, access): &(RowId, AccessRequest)| cancel_bet(*id, access.clone()));
+    create_effect(move |_| {
+        if let Some(Ok(())) = cancel.value().get() {
+            refresh.set(!refresh.get_untracked());
+        }
+    });
+
+    view! {
+        <UnwrapResource resource=position view=move |position| {
+            if position.bets.is_empty() {
+                return view!{}.into_view();
+            }
+            let scenario = |side: bool| {
+                let net = position.net(side, ratio, judge_share_ppm);
+                view!{
+                    <Tr>
+                        <Td>{format!("{} wins", side)}{(outcome == Some(side)).then_some(" (resolved)")}</Td>
+                        <Td><SatsDisplay amount=position.payout(side, ratio, judge_share_ppm) state=state /></Td>
+                        <Td>{if net >= 0 {"+"} else {""}}<SatsDisplay amount=net state=state /></Td>
+                    </Tr>
+                }
+            };
+            view!{
+                <Collapsible>
+                    <CollapsibleHeader slot>"My position"</CollapsibleHeader>
+                    <CollapsibleBody slot><Stack spacing=Size::Em(0.6)>
+                        <p>
+                            "On true: "<SatsDisplay amount=position.on_true state=state /><br/>
+                            "On false: "<SatsDisplay amount=position.on_false state=state /><br/>
+                            "Total stake: "<SatsDisplay amount=position.stake() state=state />
+                        </p>
+                        <TableContainer><Table bordered=true>
+                            <Thead><Tr>
+                                <Th>{if outcome.is_some() {"Outcome"} else {"At current ratios, if"}}</Th>
+                                <Th>"Payout"</Th>
+                                <Th>"Net"</Th>
+                            </Tr></Thead>
+                            <Tbody>{match outcome {
+                                Some(outcome) => vec![scenario(outcome)],
+                                None => vec![scenario(true), scenario(false)],
+                            }}</Tbody>
+                        </Table></TableContainer>
+                        <Cond cond=trading view=view!{
+                            <TableContainer><Table bordered=true>
+                                <Thead><Tr>
+                                    <Th>"Bet"</Th>
+                                    <Th>"Amount"</Th>
+                                    <Th></Th>
+                                </Tr></Thead>
+                                <Tbody>{position.bets.iter().map(|bet| {
+                                    let bet_id = bet.id;
+                                    view!{
+                                        <Tr>
+                                            <Td>{bet.bet}</Td>
+                                            <Td><SatsDisplay amount=bet.amount state=state /></Td>
+                                            <Td><Button
+                                                disabled=cancel.pending()
+                                                on_click=move |_| cancel.dispatch((bet_id, access.get_untracked()))
+                                            >"Cancel"</Button></Td>
+                                        </Tr>
+                                    }
+                                }).collect::<Vec<_>>()}</Tbody>
+                            </Table></TableContainer>
+                        } />
+                        {move || match cancel.value().get() {
+                            Some(Err(e)) => Some(view!{<small>{format!("{:?}", e)}</small>}),
+                            _ => None,
+                        }}
+                    </Stack></CollapsibleBody>
+                </Collapsible>
+            }.into_view()
+        } />
+    }
+    .into_view()
+}
+#[component]
+pub fn JudgeConsensus(
+    prediction: PredictionOverviewResponse,
+    state: ReadSignal<MercadoState>,
+    refresh: RwSignal<bool>,
+) -> impl IntoView {
+    let id = prediction.id;
+    let judge_count = prediction.judge_count;
+    let states = create_local_resource(
+        move || refresh.get(),
+        move |_| get_judge_states(id, state.get_untracked().access),
+    );
+
+    view! {
+        <UnwrapResource resource=states view=move |states| {
+            let tally = JudgeTally::new(&states);
+            let warning = match prediction.state {
+                MarketState::WaitingForJudges if tally.cannot_resolve(judge_count) => Some(format!(
+                    "Too many judges refused: only {} of {} required judges can still accept, the market can't start",
+                    tally.accepted + tally.pending + tally.unknown, judge_count
+                )),
+                MarketState::WaitingForJudges if tally.missing(judge_count) > 0 => Some(format!(
+                    "{} more judge(s) need to accept before trading starts", tally.missing(judge_count)
+                )),
+                _ => None,
+            };
+            let outcome = match prediction.state {
+                MarketState::Resolved(outcome) => Some(format!("Resolved as {}", outcome)),
+                _ => None,
+            };
+            view!{
+                <p>
+                    "Judges: "{tally.nominated}" nominated, "
+                    {tally.accepted}" accepted, "
+                    {tally.refused}" refused, "
+                    {tally.decided()}" decided of "{judge_count}" required"
+                    <Cond cond={tally.unknown > 0} view=view!{
+                        <br/><HiddenJudgeStates>{tally.unknown}" of them aren't counted here."</HiddenJudgeStates>
+                    } /><br/>
+                    <ProgressBar progress=Some(tally.accepted.min(judge_count as usize) as f64) max={judge_count as f64} />
+                    <Cond cond={tally.decided() > 0} view=view!{
+                        "Decisions: "{tally.decided_true}" true, "{tally.decided_false}" false"<br/>
+                    } />
+                    {warning.map(|warning| view!{<strong>"⚠ "{warning}</strong><br/>})}
+                    {outcome.map(|outcome| view!{<kbd>{outcome}</kbd>})}
+                </p>
+            }
+        } />
+    }
+}
+/// Explains missing judge states, followed by what that means where it's shown.
+#[component]
+pub fn HiddenJudgeStates(children: Children) -> impl IntoView {
+    view! {
+        <small>"Judge states are only visible to admins and the judges themselves. "{children()}</small>
+    }
+}
+#[component]
+pub fn CreatorPanel(
+    prediction: PredictionOverviewResponse,
+    state: ReadSignal<MercadoState>,
+    refresh: RwSignal<bool>,
+) -> impl IntoView {
+    let id = prediction.id;
+    let judge_count = prediction.judge_count;
+    let waiting = prediction.state == MarketState::WaitingForJudges;
+    let states = create_local_resource(
+        move || refresh.get(),
+        move |_| get_judge_states(id, state.get_untracked().access),
+    );
+
+    view! {
+        <UnwrapResource resource=states view=move |states| {
+            let tally = JudgeTally::new(&states);
+            // A creator who isn't an admin can't tell who refused.
+            let visible = tally.unknown == 0;
+            view!{
+                <Collapsible>
+                    <CollapsibleHeader slot>{if visible {
+                        format!(
+                            "Nominations: {} accepted, {} refused, {} pending of {} required",
+                            tally.accepted, tally.refused, tally.pending, judge_count
+                        )
+                    } else {
+                        format!("Nominations: {} judge(s) for {} required", tally.nominated, judge_count)
+                    }}</CollapsibleHeader>
+                    <CollapsibleBody slot><Stack spacing=Size::Em(0.6)>
+                        <Cond cond={waiting && visible && tally.missing(judge_count) > 0} view=view!{
+                            <strong>{format!("{} more acceptance(s) needed", tally.missing(judge_count))}</strong>
+                        } />
+                        <Cond cond=!visible view=view!{
+                            <HiddenJudgeStates>
+                                "Ask an admin to check for refusals, or propose again and replace judges yourself."
+                            </HiddenJudgeStates>
+                        } />
+                        <TableContainer><Table bordered=true>
+                            <Thead><Tr>
+                                <Th>"Judge"</Th>
+                                <Th>"Nomination"</Th>
+                            </Tr></Thead>
+                            <Tbody>{states.iter().map(|(judge, judge_state)| view!{
+                                <Tr>
+                                    <Td><Username user=Some(*judge) /></Td>
+                                    <Td>{match judge_state {
+                                        Some(JudgeState::Refused) => view!{<strong>"⚠ Refused"</strong>}.into_view(),
+                                        Some(judge_state) => judge_state.to_string().into_view(),
+                                        None => "Not visible".into_view(),
+                                    }}</Td>
+                                </Tr>
+                            }).collect::<Vec<_>>()}</Tbody>
+                        </Table></TableContainer>
+                        <Cond cond={waiting && (!visible || tally.refused > 0)} view=view!{
+                            <LinkButton href=format!("/new_prediction?repropose={}", id)>
+                                {if visible {"Propose again with replacement judges"} else {"Propose again"}}
+                            </LinkButton>
+                        } />
+                    </Stack></CollapsibleBody>
+                </Collapsible>
+            }
+        } />
+    }
+}
+#[component]
 pub fn JudgeList(
     prediction: Option<RowId>,
     user: Option<UserPubKey>,
@@ -375,17 +887,20 @@ pub fn JudgeList(
         move || prediction.clone(),
         move |prediction| get_judges(prediction, user),
     );
+    let selected = create_rw_signal(vec![]);
     let table = move |judges: Vec<JudgePublic>| {
         view! {
+            <BulkNominations selected=selected state=state refresh=refresh />
             <TableContainer><Table bordered=true hoverable=true>
                 <Thead><Tr>
+                    <Th></Th>
                     <Th>"Judge"</Th>
                     <Th>"State"</Th>
                     <Th>"Actions"</Th>
                 </Tr></Thead>
                 <Tbody><For each=move || judges.clone() key=move |judge| judge.user
                 children=move |judge: JudgePublic| view!{
-                    <JudgeListItem judge=judge state=state refresh=refresh />
+                    <JudgeListItem judge=judge state=state refresh=refresh selected=selected />
                 }/></Tbody>
             </Table></TableContainer>
         }
@@ -414,6 +929,7 @@ pub fn JudgeListItem(
     judge: JudgePublic,
     state: ReadSignal<MercadoState>,
     #[prop(optional)] refresh: Option<RwSignal<bool>>,
+    #[prop(optional)] selected: Option<RwSignal<Vec<(RowId, UserPubKey)>>>,
 ) -> impl IntoView {
     let refresh = if let Some(refresh) = refresh {
         refresh
@@ -424,29 +940,28 @@ pub fn JudgeListItem(
         move || (judge.prediction, refresh.get()),
         move |(id, _)| get_prediction_overview(id),
     );
-    let accept = create_action(|request: &PostRequest<NominationRequest>| {
-        accept_nomination(request.data.clone(), request.access.clone())
-    });
-    let refuse = create_action(|request: &PostRequest<NominationRequest>| {
-        refuse_nomination(request.data.clone(), request.access.clone())
-    });
-    let decide = create_action(move |(judge, bet): &(Judge, bool)| {
-        make_decision(
-            MakeDecisionRequest {
-                decision: bet.clone(),
-                judge: judge.user,
-                prediction: judge.prediction.clone(),
-            },
-            state.get().access.unwrap(),
-        )
-    });
     let judge_priv = create_local_resource(
         move || refresh.get(),
         move |_| get_judge(judge.prediction, judge.user, state),
     );
     view! {
         <Tr>
-            <Td><Username user= Some(judge.user) /></Td>
+            <Td>{selected.map(|selected| view!{
+                <UnwrapResourceForUser
+                    user=judge.user
+                    state=state
+                    resource=judge_priv
+                    view=move |judge| view!{
+                        <Cond cond=judge.state == JudgeState::Nominated view=view!{
+                            <NominationCheckbox prediction=judge.prediction user=judge.user selected=selected />
+                        } />
+                    }
+                />
+            })}</Td>
+            <Td>
+                <Username user= Some(judge.user) />" "
+                <Link href=format!("/judge/{}", judge.user)><small>"Track record"</small></Link>
+            </Td>
             <Td><UnwrapResourceForUser
                 user=judge.user
                 state=state
@@ -457,52 +972,83 @@ pub fn JudgeListItem(
             user=judge.user
             state=state
             resource=judge_priv
-            view= move |judge| {
-                match prediction.get().transpose().ok().flatten().map(|prediction| prediction.state).unwrap_or(MarketState::Trading) {
-                    MarketState::WaitingForJudges => {
-                        view! {
-                            <Button on_click=move |_| {
-                                accept.dispatch(PostRequest {
-                                    data: NominationRequest {user: judge.user, prediction: judge.prediction},
-                                    access: state.get().access.unwrap()});
-                                refresh.set(!refresh.get());
-                            }>
-                                "Accept"
-                            </Button>
-                            <Button on_click=move |_| {
-                                refuse.dispatch(PostRequest {
-                                    data: NominationRequest {user: judge.user, prediction: judge.prediction},
-                                    access: state.get().access.unwrap()});
-                                refresh.set(!refresh.get());
-                            }>
-                                "Refuse"
-                            </Button>
-                        }.into_view()
-                    }
-                    MarketState::WaitingForDecision => {
-                        view! {
-                            <Button on_click=move |_| {
-                                decide.dispatch((judge, true));
-                                refresh.set(!refresh.get());
-                            }>
-                                "Decide True"
-                            </Button>
-                            <Button on_click=move |_| {
-                                decide.dispatch((judge, false));
-                                refresh.set(!refresh.get());
-                            }>
-                                "Decide False"
-                            </Button>
-
-                        }.into_view()
-                    }
-                    _ => {view!{}.into_view()}
-                }
-                } />
-            </Td>
+            view=move |judge| move || {
+                let market = prediction.get().transpose().ok().flatten().map(|prediction| prediction.state);
+                market.map(|market| view!{<JudgeActions judge=judge market=market state=state refresh=refresh />})
+            }
+            /></Td>
         </Tr>
     }
 }
+/// Accept and refuse buttons for a nomination, or the decision buttons once
+/// the market waits for the judges. Flips `refresh` after each response.
+#[component]
+pub fn JudgeActions(
+    judge: Judge,
+    market: MarketState,
+    state: ReadSignal<MercadoState>,
+    refresh: RwSignal<bool>,
+) -> impl IntoView {
+    let accept = create_action(|request: &PostRequest<NominationRequest>| {
+        accept_nomination(request.data.clone(), request.access.clone())
+    });
+    let refuse = create_action(|request: &PostRequest<NominationRequest>| {
+        refuse_nomination(request.data.clone(), request.access.clone())
+    });
+    let decide = create_action(|(request, access): &(MakeDecisionRequest, AccessRequest)| {
+        make_decision(request.clone(), access.clone())
+    });
+    create_effect(move |previous: Option<usize>| {
+        let versions = accept.version().get() + refuse.version().get() + decide.version().get();
+        if previous.is_some_and(|previous| previous != versions) {
+            refresh.set(!refresh.get_untracked());
+        }
+        versions
+    });
+    let error = move || {
+        [accept.value().get(), refuse.value().get()]
+            .into_iter()
+            .chain([decide.value().get()])
+            .flatten()
+            .find_map(|result| result.err())
+    };
+    let access = if let Some(access) = state.get_untracked().access {
+        store_value(access)
+    } else {
+        return view! {}.into_view();
+    };
+    let nomination = move || PostRequest {
+        data: NominationRequest {
+            user: judge.user,
+            prediction: judge.prediction,
+        },
+        access: access.get_value(),
+    };
+    let decision = move |decision: bool| {
+        let request = MakeDecisionRequest {
+            decision,
+            judge: judge.user,
+            prediction: judge.prediction,
+        };
+        (request, access.get_value())
+    };
+    let nominated = judge.state == JudgeState::Nominated && market == MarketState::WaitingForJudges;
+    let decidable =
+        judge.state == JudgeState::Accepted && market == MarketState::WaitingForDecision;
+
+    view! {
+        <Cond cond=nominated view=view!{
+            <Button on_click=move |_| accept.dispatch(nomination())>"Accept"</Button>
+            <Button on_click=move |_| refuse.dispatch(nomination())>"Refuse"</Button>
+        } />
+        <Cond cond=decidable view=view!{
+            <Button on_click=move |_| decide.dispatch(decision(true))>"Decide True"</Button>
+            <Button on_click=move |_| decide.dispatch(decision(false))>"Decide False"</Button>
+        } />
+        <small>{error}</small>
+    }
+    .into_view()
+}
 #[component]
 pub fn Cond<V>(cond: bool, view: V) -> impl IntoView
 where
@@ -553,7 +1099,7 @@ pub fn BetList(
                 children=move |bet: Bet| view!{
                     <Tr>
                         <Td>{bet.bet}</Td>
-                        <Td>{bet.amount}</Td>
+                        <Td><SatsDisplay amount=bet.amount state=state /></Td>
                         <Cond cond=user.is_none() view=view!{<Td><Username user=Some(bet.user) /></Td>}/>
                         <Cond cond=prediction.is_none() view=view!{<Td><Link href={format!("/prediction/{}", bet.prediction)}>"Prediction"</Link></Td>}/>
                         <Td><Button on_click=move |_| {
@@ -585,6 +1131,118 @@ pub fn BetList(
     }
 }
 #[component]
+pub fn SatsDisplay(
+    #[prop(into)] amount: i128,
+    state: ReadSignal<MercadoState>,
+    #[prop(optional)] no_fiat: Option<bool>,
+) -> impl IntoView {
+    move || {
+        let state = state.get();
+        let sats = sats::format(amount, state.unit);
+        match (no_fiat, state.price_feed, state.price) {
+            (None | Some(false), Some(feed), Some(price)) if feed == price.feed => {
+                let title = format!("{} per BTC as of {}", price.per_btc, price.fetched);
+                view! {
+                    {sats}" "
+                    <small class={if price.is_stale() {"stale-price"} else {""}} title=title>
+                        {price.format(amount)}{if price.is_stale() {" (outdated)"} else {""}}
+                    </small>
+                }
+                .into_view()
+            }
+            _ => sats.into_view(),
+        }
+    }
+}
+#[component]
+pub fn SatsInput(
+    value: RwSignal<Sats>,
+    state: ReadSignal<MercadoState>,
+    #[prop(optional)] min: Option<Sats>,
+    #[prop(optional)] max: Option<Sats>,
+    /// Caps the amount at the available balance of this user and offers a "Max" button
+    #[prop(optional_no_strip)]
+    available_of: Option<UserPubKey>,
+    /// Set to whether the entered amount passes validation
+    #[prop(optional)]
+    valid: Option<RwSignal<bool>>,
+) -> impl IntoView {
+    let valid = valid.unwrap_or_else(|| create_rw_signal(true));
+    let unit = move || state.get().unit;
+    let text = create_rw_signal(sats::to_input(
+        value.get_untracked(),
+        state.get_untracked().unit,
+    ));
+    create_effect(move |previous: Option<Unit>| {
+        let unit = unit();
+        if previous.is_some_and(|previous| previous != unit) {
+            text.set(sats::to_input(value.get_untracked(), unit));
+        }
+        unit
+    });
+    let available = create_local_resource(
+        move || (),
+        move |_| async move {
+            match (available_of, state.get_untracked().access) {
+                (Some(user), Some(access)) => get_available_balance(user, access).await.map(Some),
+                _ => Ok(None),
+            }
+        },
+    );
+    let available = move || available.get().and_then(|result| result.ok()).flatten();
+    let error = move || {
+        let amount = match sats::parse(text.get().as_str(), unit()) {
+            Ok(amount) => amount,
+            Err(e) => return Some(e),
+        };
+        if let Some(min) = min.filter(|min| amount < *min) {
+            return Some(format!("The minimum is {}", sats::format(min, unit())));
+        }
+        if let Some(max) = max.filter(|max| amount > *max) {
+            return Some(format!("The maximum is {}", sats::format(max, unit())));
+        }
+        if let Some(available) = available().filter(|available| amount > *available) {
+            return Some(format!(
+                "Only {} available",
+                sats::format(available, unit())
+            ));
+        }
+        None
+    };
+    create_effect(move |_| {
+        if let Ok(amount) = sats::parse(text.get().as_str(), unit()) {
+            value.set(amount);
+        }
+        valid.set(error().is_none());
+    });
+    // Amounts set from outside, e.g. by presets
+    create_effect(move |_| {
+        let amount = value.get();
+        let unit = state.get_untracked().unit;
+        if sats::parse(text.get_untracked().as_str(), unit) != Ok(amount) {
+            text.set(sats::to_input(amount, unit));
+        }
+    });
+
+    view! {
+        <div>
+            <input type="text" inputmode="decimal"
+                prop:value=move || text.get()
+                on:input=move |ev| text.set(event_target_value(&ev))
+            />
+            " "{move || unit().to_string()}" "
+            {available_of.map(|_| view!{
+                <Button on_click=move |_| {
+                    if let Some(available) = available() {
+                        text.set(sats::to_input(available, unit()));
+                    }
+                }>"Max"</Button>
+            })}
+            <br/><small>{error}</small>
+        </div>
+    }
+}
+#[component]
 pub fn ShortenedString(
     mut string: String,
     #[prop(optional)] no_clipboard: Option<bool>,
@@ -648,8 +1306,155 @@ pub fn Username(
     }}
 }
 #[component]
-pub fn MyBets(state: ReadSignal<MercadoState>) -> impl IntoView {
-    let user = if let Some(user) = state.get_untracked().user {
+pub fn Settings(
+    state: ReadSignal<MercadoState>,
+    set_state: WriteSignal<MercadoState>,
+) -> impl IntoView {
+    let feed = state.get_untracked().price_feed;
+    let (url, set_url) = create_signal(feed.clone().map(|feed| feed.url).unwrap_or_default());
+    let (pointer, set_pointer) =
+        create_signal(feed.clone().map(|feed| feed.pointer).unwrap_or_default());
+    let (currency, set_currency) =
+        create_signal(feed.map(|feed| feed.currency).unwrap_or_default());
+    let limits = state.get_untracked().withdrawal_limits;
+    let confirm_above = create_rw_signal(limits.confirm_above);
+    let (confirm_above_percent, set_confirm_above_percent) =
+        create_signal(limits.confirm_above_percent as f64);
+    let daily_cap = create_rw_signal(limits.daily_cap);
+    let (cooldown, set_cooldown) = create_signal(limits.cooldown_minutes as f64);
+    // Looser limits only take effect after signing a fresh login challenge,
+    // so a hijacked session can't lift them.
+    let pending_limits = create_rw_signal::<Option<WithdrawalLimits>>(None);
+    let limits_message = create_rw_signal::<Option<String>>(None);
+    let limits_challenge =
+        create_action(move |user: &UserPubKey| create_login_challenge(user.to_string()));
+    let (limits_signature, set_limits_signature) = create_signal(String::new());
+    let confirm_limits = create_action(
+        move |(limits, (challenge, signature)): &(WithdrawalLimits, (String, String))| {
+            let (limits, challenge, signature) =
+                (limits.clone(), challenge.clone(), signature.clone());
+            let access = state.get_untracked().access;
+            async move {
+                let user = access.ok_or("Not logged in".to_string())?.user;
+                reauthenticate(user, signature, challenge).await?;
+                Ok::<_, String>(limits)
+            }
+        },
+    );
+    create_effect(move |_| match confirm_limits.value().get() {
+        Some(Ok(limits)) => {
+            set_state.update(|state| state.withdrawal_limits = limits);
+            pending_limits.set(None);
+            limits_message.set(Some("Limits saved".to_string()));
+        }
+        Some(Err(e)) => limits_message.set(Some(e)),
+        None => {}
+    });
+    let save_limits = move |limits: WithdrawalLimits| {
+        let state = state.get_untracked();
+        if !limits.is_looser_than(&state.withdrawal_limits) {
+            set_state.update(|state| state.withdrawal_limits = limits);
+            pending_limits.set(None);
+            limits_message.set(Some("Limits saved".to_string()));
+        } else if let Some(access) = state.access {
+            pending_limits.set(Some(limits));
+            limits_message.set(None);
+            limits_challenge.dispatch(access.user);
+        } else {
+            limits_message.set(Some("Log in to loosen the withdrawal limits".to_string()));
+        }
+    };
+    let confirm = move || {
+        let challenge = limits_challenge
+            .value()
+            .get_untracked()
+            .and_then(|challenge| challenge.ok())
+            .context("No challenge to sign yet")?;
+        let limits = pending_limits
+            .get_untracked()
+            .context("Nothing to confirm")?;
+        confirm_limits.dispatch((limits, (challenge, limits_signature.get_untracked())));
+        Ok::<(), anyhow::Error>(())
+    };
+
+    view! {
+        <Stack spacing=Size::Em(1.0)>
+            <H3>"Fiat reference price"</H3>
+            <p><small>
+                "Shows approximate fiat values next to sat amounts. The endpoint must return JSON; "
+                "the JSON pointer selects the price of one BTC, e.g. /bitcoin/eur."
+            </small></p>
+            <Box style="width: 50%">
+                <TextInput get=url set=set_url placeholder="Price endpoint URL" />
+                <TextInput get=pointer set=set_pointer placeholder="JSON pointer, e.g. /bitcoin/eur" />
+                <TextInput get=currency set=set_currency placeholder="Currency, e.g. EUR" />
+            </Box>
+            <ButtonGroup>
+                <Button on_click=move |_| set_state.update(|state| {
+                    state.price_feed = Some(PriceFeed {
+                        url: url.get(),
+                        pointer: pointer.get(),
+                        currency: currency.get(),
+                    });
+                })>"Save"</Button>
+                <Button on_click=move |_| set_state.update(|state| {
+                    state.price_feed = None;
+                    state.price = None;
+                })>"Disable"</Button>
+            </ButtonGroup>
+            <p>{move || match state.get().price {
+                Some(price) => format!("Last price: {} {} per BTC, fetched {}", price.per_btc, price.feed.currency, price.fetched),
+                None => "No price fetched yet".to_string(),
+            }}</p>
+            <H3>"Withdrawal limits"</H3>
+            <div>
+                <label>"Confirm withdrawals above"</label>
+                <SatsInput value=confirm_above state=state />
+            </div>
+            <div>
+                <NumberInput get=confirm_above_percent set=set_confirm_above_percent step=5.0 min=0.0 max=100.0 />
+                <label>"Confirm withdrawals above this share of the available balance (%)"</label>
+            </div>
+            <div>
+                <label>"Daily withdrawal cap (0 for no cap)"</label>
+                <SatsInput value=daily_cap state=state />
+            </div>
+            <div>
+                <NumberInput get=cooldown set=set_cooldown step=1.0 min=0.0 />
+                <label>"Block withdrawals for this many minutes after logging in"</label>
+            </div>
+            <ButtonGroup>
+                <Button on_click=move |_| save_limits(WithdrawalLimits {
+                    confirm_above: confirm_above.get(),
+                    confirm_above_percent: confirm_above_percent.get().clamp(0.0, 100.0) as u8,
+                    daily_cap: daily_cap.get(),
+                    cooldown_minutes: cooldown.get().max(0.0) as u32,
+                })>"Save limits"</Button>
+                <Button on_click=move |_| save_limits(WithdrawalLimits::default())>"Reset to defaults"</Button>
+            </ButtonGroup>
+            {move || pending_limits.get().map(|_| view!{
+                <div>
+                    <p>"These limits are less strict than the current ones. Sign this challenge with your key to confirm: "
+                        <kbd>{move || limits_challenge.value().get().map(|challenge| challenge.unwrap_or_else(|e| e))}</kbd>
+                    </p>
+                    <TextInput get=limits_signature set=set_limits_signature placeholder="ECDSA Signature" />
+                    <ButtonGroup>
+                        <Button on_click=move |_| {
+                            if let Err(e) = confirm() {
+                                limits_message.set(Some(e.to_string()));
+                            }
+                        }>"Confirm limits"</Button>
+                        <Button on_click=move |_| pending_limits.set(None)>"Cancel"</Button>
+                    </ButtonGroup>
+                </div>
+            })}
+            <p>{move || limits_message.get()}</p>
+        </Stack>
+    }
+}
+#[component]
+pub fn MyBets(state: ReadSignal<MercadoState>) -> impl IntoView {
+    let user = if let Some(user) = state.get_untracked().user {
         if let UserRole::User = user.role {
             Some(user.user)
         } else {
@@ -660,8 +1465,148 @@ pub fn MyBets(state: ReadSignal<MercadoState>) -> impl IntoView {
     };
 
     view! {
-        <BetList state=state prediction=None user=user />
+        <HistoryExport kind=ExportKind::Bets user=user state=state />
+        <Portfolio state=state />
+        <Cond cond=user.is_none() view=view!{
+            <H3>"All bets"</H3>
+            <BetList state=state prediction=None user=user collapsable=true />
+        } />
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PortfolioShow {
+    All,
+    Open,
+    Resolved,
+    Refunded,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PortfolioSort {
+    TradingEnd,
+    Name,
+    Stake,
+    Result,
+}
+#[component]
+pub fn Portfolio(state: ReadSignal<MercadoState>) -> impl IntoView {
+    let access = if let Some(access) = state.get_untracked().access {
+        create_rw_signal(access)
+    } else {
+        return view! {<p>"Login to see your bets"</p>}.into_view();
+    };
+    let holdings = create_local_resource(move || access.get(), get_portfolio);
+    let show = create_rw_signal(PortfolioShow::All);
+    let shows = [
+        (PortfolioShow::All, "All"),
+        (PortfolioShow::Open, "Open"),
+        (PortfolioShow::Resolved, "Resolved"),
+        (PortfolioShow::Refunded, "Refunded"),
+    ];
+    let sort = create_rw_signal(PortfolioSort::TradingEnd);
+    let sorts = [
+        (PortfolioSort::TradingEnd, "Trading end"),
+        (PortfolioSort::Name, "Name"),
+        (PortfolioSort::Stake, "Stake"),
+        (PortfolioSort::Result, "Result"),
+    ];
+
+    view! {
+        <H3>"Portfolio"</H3>
+        <UnwrapResource resource=holdings view=move |Partial { items: holdings, failed }| {
+            let exposure: i128 = holdings.iter().filter(|holding| holding.is_open()).map(|holding| holding.position.stake() as i128).sum();
+            let realised: i128 = holdings.iter().filter_map(|holding| holding.realised()).sum();
+            let (worst, best) = holdings
+                .iter()
+                .filter_map(|holding| holding.projected())
+                .fold((0, 0), |(worst, best), (on_true, on_false)| {
+                    (worst + on_true.min(on_false), best + on_true.max(on_false))
+                });
+            let filter = show.get();
+            let mut holdings = holdings
+                .into_iter()
+                .filter(|holding| match filter {
+                    PortfolioShow::All => true,
+                    PortfolioShow::Open => holding.is_open(),
+                    PortfolioShow::Resolved => holding.realised().is_some(),
+                    PortfolioShow::Refunded => holding.is_refunded(),
+                })
+                .collect::<Vec<_>>();
+            match sort.get() {
+                PortfolioSort::TradingEnd => holdings.sort_by_key(|holding| std::cmp::Reverse(holding.prediction.trading_end)),
+                PortfolioSort::Name => holdings.sort_by(|a, b| a.prediction.name.cmp(&b.prediction.name)),
+                PortfolioSort::Stake => holdings.sort_by_key(|holding| std::cmp::Reverse(holding.position.stake())),
+                PortfolioSort::Result => holdings.sort_by_key(|holding| {
+                    std::cmp::Reverse(holding.realised().or(holding.projected().map(|(on_true, on_false)| on_true.max(on_false))))
+                }),
+            }
+            view!{
+                <p>
+                    "Exposure in open markets: "<SatsDisplay amount=exposure state=state /><br/>
+                    "Projected for open markets: "<SatsDisplay amount=worst state=state />" to "<SatsDisplay amount=best state=state /><br/>
+                    "Realised: "<SatsDisplay amount=realised state=state />
+                </p>
+                <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(1.0)>
+                    <label>"Show "
+                        <select on:change=move |ev| {
+                            let value = event_target_value(&ev);
+                            if let Some((which, _)) = shows.iter().find(|(_, label)| *label == value) {
+                                show.set(*which);
+                            }
+                        }>
+                            {shows.into_iter().map(|(which, label)| view!{
+                                <option value=label selected=move || show.get() == which>{label}</option>
+                            }).collect::<Vec<_>>()}
+                        </select>
+                    </label>
+                    <label>"Sort by "
+                        <select on:change=move |ev| {
+                            let value = event_target_value(&ev);
+                            if let Some((by, _)) = sorts.iter().find(|(_, label)| *label == value) {
+                                sort.set(*by);
+                            }
+                        }>
+                            {sorts.into_iter().map(|(by, label)| view!{
+                                <option value=label selected=move || sort.get() == by>{label}</option>
+                            }).collect::<Vec<_>>()}
+                        </select>
+                    </label>
+                </Stack>
+                <TableContainer><Table bordered=true hoverable=true>
+                    <Thead><Tr>
+                        <Th>"Prediction"</Th>
+                        <Th>"State"</Th>
+                        <Th>"On true"</Th>
+                        <Th>"On false"</Th>
+                        <Th>"Result"</Th>
+                    </Tr></Thead>
+                    <Tbody>{holdings.into_iter().map(|holding| {
+                        let result = match (holding.realised(), holding.projected()) {
+                            (Some(realised), _) => view!{<SatsDisplay amount=realised state=state />}.into_view(),
+                            (None, Some((on_true, on_false))) => view!{
+                                <small>"If true: "</small><SatsDisplay amount=on_true state=state /><br/>
+                                <small>"If false: "</small><SatsDisplay amount=on_false state=state />
+                            }.into_view(),
+                            (None, None) if holding.is_refunded() => view!{
+                                <small>"Refunded: "</small><SatsDisplay amount=holding.position.stake() state=state />
+                            }.into_view(),
+                            (None, None) => "-".into_view(),
+                        };
+                        view!{
+                            <Tr>
+                                <Td><Link href=format!("/prediction/{}", holding.prediction.id)>{holding.prediction.name.clone()}</Link></Td>
+                                <Td>{holding.prediction.state.to_string()}</Td>
+                                <Td><SatsDisplay amount=holding.position.on_true state=state /></Td>
+                                <Td><SatsDisplay amount=holding.position.on_false state=state /></Td>
+                                <Td>{result}</Td>
+                            </Tr>
+                        }
+                    }).collect::<Vec<_>>()}</Tbody>
+                </Table></TableContainer>
+                <LoadFailures failed=failed />
+            }
+        } />
     }
+    .into_view()
 }
 #[component]
 pub fn MyJudges(state: ReadSignal<MercadoState>) -> impl IntoView {
@@ -676,70 +1621,662 @@ pub fn MyJudges(state: ReadSignal<MercadoState>) -> impl IntoView {
     };
 
     view! {
-        <JudgeList state=state prediction=None user=user />
+        <HistoryExport kind=ExportKind::Judges user=user state=state />
+        <JudgeDashboard state=state />
+        <Cond cond=user.is_none() view=view!{
+            <H3>"All judges"</H3>
+            <JudgeList state=state prediction=None user=user collapsable=true />
+        } />
+    }
+}
+/// Predictions that couldn't be loaded, see `Partial`.
+#[component]
+pub fn LoadFailures(failed: Vec<(RowId, String)>) -> impl IntoView {
+    let count = failed.len();
+    view! {
+        <Cond cond={count > 0} view=view!{
+            <H3>"Couldn't load: "{count}</H3>
+            <ul>{failed.into_iter().map(|(prediction, e)| view!{
+                <li><Link href=format!("/prediction/{}", prediction)>"Prediction "{prediction}</Link>": "{e}</li>
+            }).collect::<Vec<_>>()}</ul>
+        } />
+    }
+}
+fn countdown(until: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let left = until - now;
+    if left <= Duration::zero() {
+        return "overdue".to_string();
+    }
+    format!(
+        "{} days {} hours {} minutes left",
+        left.num_days(),
+        left.num_hours() % 24,
+        left.num_minutes() % 60
+    )
+}
+#[component]
+pub fn JudgeDashboard(state: ReadSignal<MercadoState>) -> impl IntoView {
+    let access = if let Some(access) = state.get_untracked().access {
+        create_rw_signal(access)
+    } else {
+        return view! {<p>"Login to see your judge assignments"</p>}.into_view();
+    };
+    let refresh = create_rw_signal(true);
+    let tasks = create_local_resource(
+        move || (access.get(), refresh.get()),
+        move |(a, _)| get_judge_tasks(a.user, a),
+    );
+    let now = create_rw_signal(Utc::now());
+    if let Ok(handle) = set_interval_with_handle(
+        move || now.set(Utc::now()),
+        std::time::Duration::from_secs(30),
+    ) {
+        on_cleanup(move || handle.clear());
+    }
+
+    let me = access.get_untracked().user;
+    // Outside the resource, so the selection and the results of a bulk
+    // response survive the refresh it triggers.
+    let selected = create_rw_signal(vec![]);
+    view! {
+        <LinkButton href=format!("/judge/{}", me)>"My track record"</LinkButton>
+        <BulkNominations selected=selected state=state refresh=refresh />
+        <UnwrapResource resource=tasks view=move |Partial { items: tasks, failed }| {
+            let (nominations, rest): (Vec<_>, Vec<_>) = tasks.into_iter().partition(|task| {
+                task.judge.state == JudgeState::Nominated
+                    && task.prediction.state == MarketState::WaitingForJudges
+            });
+            let (mut due, mut completed): (Vec<_>, Vec<_>) = rest.into_iter().partition(|task| {
+                task.judge.state == JudgeState::Accepted && is_open(&task.prediction.state)
+            });
+            due.sort_by_key(|task| task.deadline());
+            completed.sort_by_key(|task| std::cmp::Reverse(task.deadline()));
+            view!{
+                <H3>"Nominations awaiting response: "{nominations.len()}</H3>
+                <JudgeTaskTable tasks=nominations state=state refresh=refresh now=now selected=selected />
+                <H3>"Decisions due: "{due.len()}</H3>
+                <JudgeTaskTable tasks=due state=state refresh=refresh now=now />
+                <H3>"Completed: "{completed.len()}</H3>
+                <JudgeTaskTable tasks=completed state=state refresh=refresh now=now />
+                <LoadFailures failed=failed />
+            }
+        } />
+    }
+    .into_view()
+}
+#[component]
+pub fn JudgeTrackRecord(state: ReadSignal<MercadoState>) -> impl IntoView {
+    let params = use_params_map();
+    let id = params.with_untracked(|p| p.get("id").cloned());
+    let user = if let Ok(user) = id.unwrap_or_default().parse::<UserPubKey>() {
+        user
+    } else {
+        return view! {<p>"Invalid public key"</p>}.into_view();
+    };
+    let earnings = create_local_resource(
+        move || state.get().access,
+        move |access| get_judge_earnings(user, access),
+    );
+
+    view! {
+        <Stack spacing=Size::Em(1.0)>
+            <H3>"Track record of "<Username user=Some(user) /></H3>
+            <UnwrapResource resource=earnings view=move |earnings| {
+                let earned: i128 = earnings.iter().filter_map(|earning| earning.earned()).map(|sats| sats as i128).sum();
+                let expected: i128 = earnings.iter().filter_map(|earning| earning.expected()).map(|sats| sats as i128).sum();
+                let agreements = earnings.iter().filter_map(|earning| earning.agrees()).collect::<Vec<_>>();
+                let agreed = agreements.iter().filter(|agrees| **agrees).count();
+                let hidden = earnings.iter().filter(|earning| earning.state.is_none()).count();
+                let mut resolved = earnings
+                    .iter()
+                    .filter_map(|earning| earning.earned().map(|sats| (earning.resolved_at(), sats as i64)))
+                    .collect::<Vec<_>>();
+                resolved.sort_by_key(|(time, _)| *time);
+                let cumulative = resolved
+                    .into_iter()
+                    .scan(0, |total, (time, sats)| {
+                        *total += sats;
+                        Some((time, *total))
+                    })
+                    .collect::<Vec<_>>();
+                view!{
+                    <p>
+                        "Predictions judged: "{earnings.len()}<br/>
+                        "Earned: "<SatsDisplay amount=earned state=state /><br/>
+                        "Expected from open markets: "<SatsDisplay amount=expected state=state /><br/>
+                        "Agreed with the outcome: "{agreed}" of "{agreements.len()}" decisions"
+                    </p>
+                    <Cond cond={hidden > 0} view=view!{
+                        <p><HiddenJudgeStates>
+                            "Decisions and earnings of "{hidden}" prediction(s) aren't included above."
+                        </HiddenJudgeStates></p>
+                    } />
+                    <LineChart series=vec![Series {
+                        label: "Earned".to_string(),
+                        color: "#e66956",
+                        points: cumulative,
+                    }] state=state />
+                    <TableContainer><Table bordered=true hoverable=true>
+                        <Thead><Tr>
+                            <Th>"Prediction"</Th>
+                            <Th>"Market"</Th>
+                            <Th>"Judge state"</Th>
+                            <Th>"Decision"</Th>
+                            <Th>"Agrees with outcome"</Th>
+                            <Th>"Share"</Th>
+                        </Tr></Thead>
+                        <Tbody>{earnings.into_iter().map(|earning| {
+                            let share = match (earning.earned(), earning.expected()) {
+                                (Some(earned), _) => view!{<SatsDisplay amount=earned state=state />}.into_view(),
+                                (None, Some(expected)) => view!{"~"<SatsDisplay amount=expected state=state />" (expected)"}.into_view(),
+                                (None, None) => "-".into_view(),
+                            };
+                            view!{
+                                <Tr>
+                                    <Td><Link href=format!("/prediction/{}", earning.prediction.id)>{earning.prediction.name.clone()}</Link></Td>
+                                    <Td>{earning.prediction.state.to_string()}</Td>
+                                    <Td>{earning.state.as_ref().map(|state| state.to_string()).unwrap_or("Not visible".to_string())}</Td>
+                                    <Td>{earning.decision().map(|decision| decision.to_string()).unwrap_or("-".to_string())}</Td>
+                                    <Td>{earning.agrees().map(|agrees| if agrees {"Yes"} else {"No"}).unwrap_or("-")}</Td>
+                                    <Td>{share}</Td>
+                                </Tr>
+                            }
+                        }).collect::<Vec<_>>()}</Tbody>
+                    </Table></TableContainer>
+                }
+            } />
+        </Stack>
+    }
+    .into_view()
+}
+#[component]
+pub fn JudgeTaskTable(
+    tasks: Vec<JudgeTask>,
+    state: ReadSignal<MercadoState>,
+    refresh: RwSignal<bool>,
+    now: RwSignal<DateTime<Utc>>,
+    #[prop(optional)] selected: Option<RwSignal<Vec<(RowId, UserPubKey)>>>,
+) -> impl IntoView {
+    if tasks.is_empty() {
+        return view! {<p><small>"Nothing here"</small></p>}.into_view();
+    }
+
+    view! {
+        <TableContainer><Table bordered=true hoverable=true>
+            <Thead><Tr>
+                <Cond cond=selected.is_some() view=view!{<Th></Th>} />
+                <Th>"Prediction"</Th>
+                <Th>"Market"</Th>
+                <Th>"My state"</Th>
+                <Th>"Decision deadline"</Th>
+                <Th>"Actions"</Th>
+            </Tr></Thead>
+            <Tbody>{tasks.into_iter().map(|task| {
+                let (user, prediction) = (task.judge.user, task.judge.prediction);
+                let deadline = task.deadline();
+                let nominated = task.judge.state == JudgeState::Nominated
+                    && task.prediction.state == MarketState::WaitingForJudges;
+                view!{
+                    <Tr>
+                        {selected.map(|selected| view!{
+                            <Td><Cond cond=nominated view=view!{
+                                <NominationCheckbox prediction=prediction user=user selected=selected />
+                            } /></Td>
+                        })}
+                        <Td><Link href=format!("/prediction/{}", task.prediction.id)>{task.prediction.name.clone()}</Link></Td>
+                        <Td>{task.prediction.state.to_string()}</Td>
+                        <Td>{task.judge.state.to_string()}</Td>
+                        <Td>{deadline.format("%Y-%m-%d %H:%M").to_string()}<br/>
+                            <Cond cond=is_open(&task.prediction.state) view=view!{
+                                <small>{move || countdown(deadline, now.get())}</small>
+                            } />
+                        </Td>
+                        <Td><JudgeActions judge=task.judge market=task.prediction.state.clone() state=state refresh=refresh /></Td>
+                    </Tr>
+                }
+            }).collect::<Vec<_>>()}</Tbody>
+        </Table></TableContainer>
+    }
+    .into_view()
+}
+#[component]
+pub fn NominationCheckbox(
+    prediction: RowId,
+    user: UserPubKey,
+    selected: RwSignal<Vec<(RowId, UserPubKey)>>,
+) -> impl IntoView {
+    let key = (prediction, user);
+    view! {
+        <input
+            type="checkbox"
+            prop:checked=move || selected.with(|selected| selected.contains(&key))
+            on:change=move |e| {
+                let checked = event_target_checked(&e);
+                selected.update(|selected| {
+                    selected.retain(|other| *other != key);
+                    if checked {
+                        selected.push(key);
+                    }
+                });
+            }
+        />
+    }
+}
+#[component]
+pub fn BulkNominations(
+    selected: RwSignal<Vec<(RowId, UserPubKey)>>,
+    state: ReadSignal<MercadoState>,
+    refresh: RwSignal<bool>,
+) -> impl IntoView {
+    let access = if let Some(access) = state.get_untracked().access {
+        create_rw_signal(access)
+    } else {
+        return view! {}.into_view();
+    };
+    let done = create_rw_signal(0);
+    let total = create_rw_signal(0);
+    let respond = create_action(move |accept: &bool| {
+        let accept = *accept;
+        let nominations = selected
+            .get_untracked()
+            .into_iter()
+            .map(|(prediction, user)| NominationRequest { user, prediction })
+            .collect::<Vec<_>>();
+        done.set(0);
+        total.set(nominations.len());
+        async move {
+            let results =
+                respond_to_nominations(nominations, accept, access.get_untracked(), |handled| {
+                    done.set(handled)
+                })
+                .await;
+            selected.set(vec![]);
+            refresh.set(!refresh.get_untracked());
+            (accept, results)
+        }
+    });
+    let idle = Signal::derive(move || {
+        respond.pending().get() || selected.with(|selected| selected.is_empty())
+    });
+
+    view! {
+        <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(0.6)>
+            <span>{move || format!("{} selected", selected.with(|selected| selected.len()))}</span>
+            <Button disabled=idle on_click=move |_| respond.dispatch(true)>"Accept selected"</Button>
+            <Button disabled=idle on_click=move |_| respond.dispatch(false)>"Refuse selected"</Button>
+        </Stack>
+        {move || respond.pending().get().then(|| view!{<BatchProgress done=done total=total verb="handled" />})}
+        {move || respond.value().get().map(|(accept, results)| view!{
+            <TableContainer><Table bordered=true>
+                <Thead><Tr>
+                    <Th>"Prediction"</Th>
+                    <Th>{if accept {"Accepted"} else {"Refused"}}</Th>
+                </Tr></Thead>
+                <Tbody>{results.into_iter().map(|(nomination, result)| view!{
+                    <Tr>
+                        <Td><Link href=format!("/prediction/{}", nomination.prediction)>{nomination.prediction.to_string()}</Link></Td>
+                        <Td>{match result {
+                            Ok(()) => "Yes".to_string(),
+                            Err(e) => format!("Failed: {:?}", e),
+                        }}</Td>
+                    </Tr>
+                }).collect::<Vec<_>>()}</Tbody>
+            </Table></TableContainer>
+        })}
+    }
+    .into_view()
+}
+#[component]
+pub fn BatchProgress(
+    done: RwSignal<usize>,
+    total: RwSignal<usize>,
+    verb: &'static str,
+) -> impl IntoView {
+    move || {
+        view! {
+            <ProgressBar progress=Some(done.get() as f64) max=total.get() as f64 />
+            <small>{format!("{} of {} {}", done.get(), total.get(), verb)}</small>
+        }
+    }
+}
+#[component]
+pub fn HistoryExport(
+    kind: ExportKind,
+    user: Option<UserPubKey>,
+    state: ReadSignal<MercadoState>,
+) -> impl IntoView {
+    let access = if let Some(access) = state.get_untracked().access {
+        access
+    } else {
+        return view! {}.into_view();
+    };
+    let from = create_rw_signal::<Option<NaiveDate>>(None);
+    let to = create_rw_signal::<Option<NaiveDate>>(None);
+    let export = create_action(move |format: &ExportFormat| {
+        export_history(
+            kind,
+            *format,
+            user,
+            from.get_untracked(),
+            to.get_untracked(),
+            access.clone(),
+        )
+    });
+    let parse_date = |ev: web_sys::Event| {
+        NaiveDate::parse_from_str(event_target_value(&ev).as_str(), "%Y-%m-%d").ok()
+    };
+
+    view! {
+        <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(1.0)>
+            <label>"From "<input type="date" on:input=move |ev| from.set(parse_date(ev)) /></label>
+            <label>"To "<input type="date" on:input=move |ev| to.set(parse_date(ev)) /></label>
+            <ButtonGroup>
+                <Button disabled=export.pending() on_click=move |_| export.dispatch(ExportFormat::Csv)>"Export CSV"</Button>
+                <Button disabled=export.pending() on_click=move |_| export.dispatch(ExportFormat::Json)>"Export JSON"</Button>
+            </ButtonGroup>
+            <small>{move || export.value().get().and_then(|result| result.err())}</small>
+        </Stack>
+    }
+    .into_view()
+}
+#[component]
+pub fn JudgePicker(
+    judges: ReadSignal<Vec<UserPubKey>>,
+    set_judges: WriteSignal<Vec<UserPubKey>>,
+    state: ReadSignal<MercadoState>,
+) -> impl IntoView {
+    let me = state
+        .get_untracked()
+        .access
+        .as_ref()
+        .map(|access| access.user);
+    let admin = move || {
+        state.with(|state| {
+            let is_admin = state
+                .user
+                .as_ref()
+                .is_some_and(|user| user.role != UserRole::User);
+            state.access.clone().filter(|_| is_admin)
+        })
+    };
+    let candidates = create_local_resource(admin, get_judge_candidates);
+    let (search, set_search) = create_signal(String::new());
+    let add = move |judge: UserPubKey| {
+        set_judges.update(|judges| judges.push(judge));
+        set_search.set(String::new());
+    };
+
+    view! {
+        <Box style="width: 50%">"Judges: "
+            <TextInput get=search set=set_search placeholder="Search a username or paste a public key" />
+            {move || {
+                let search = search.get().trim().to_string();
+                let judges = judges.get();
+                if search.is_empty() {
+                    return view!{}.into_view();
+                }
+                match search.parse::<UserPubKey>() {
+                    Ok(judge) => match validation::check_judge(judge, &judges, me) {
+                        Ok(()) => view!{
+                            <Button on_click=move |_| add(judge)>"Add "<Username user=Some(judge) no_clipboard=true /></Button>
+                        }.into_view(),
+                        Err(e) => view!{<small class="field-error">{e}</small>}.into_view(),
+                    },
+                    Err(_) if search.len() == 66 && search.chars().all(|c| c.is_ascii_hexdigit()) => {
+                        view!{<small class="field-error">"Not a valid public key"</small>}.into_view()
+                    }
+                    Err(_) => view!{}.into_view(),
+                }
+            }}
+            <UnwrapResource resource=candidates view=move |candidates| {
+                let search = search.get().trim().to_lowercase();
+                let judges = judges.get();
+                let suggestions = candidates
+                    .into_iter()
+                    .filter(|candidate| validation::check_judge(candidate.user, &judges, me).is_ok())
+                    .filter(|candidate| if search.is_empty() {
+                        candidate.nominations > 0
+                    } else {
+                        candidate.name.to_lowercase().contains(&search)
+                            || candidate.user.to_string().starts_with(&search)
+                    })
+                    .take(8)
+                    .collect::<Vec<_>>();
+                view!{
+                    <Cond cond={search.is_empty() && !suggestions.is_empty()} view=view!{
+                        <p><small>"Judges who have served before:"</small></p>
+                    } />
+                    <ul>{suggestions.into_iter().map(|candidate| {
+                        let user = candidate.user;
+                        view!{
+                            <li>
+                                <Username user=Some(user) no_clipboard=true />" "
+                                <small>{format!("judged {}, {} resolved", candidate.nominations, candidate.resolved)}" "
+                                    <Link href=format!("/judge/{}", user)>"Track record"</Link>
+                                </small>" "
+                                <Button on_click=move |_| add(user)>"Add"</Button>
+                            </li>
+                        }
+                    }).collect::<Vec<_>>()}</ul>
+                }
+            } />
+        </Box>
+    }
+}
+const WIZARD_STEPS: [&str; 4] = ["Question", "Timing", "Judges", "Review"];
+/// Wizard step on which a field is edited.
+fn wizard_step(field: Field) -> usize {
+    match field {
+        Field::Name => 0,
+        Field::TradingEnd | Field::DecisionPeriod => 1,
+        Field::JudgeShare | Field::JudgeCount | Field::Judges => 2,
     }
 }
 #[component]
 pub fn NewPrediction(state: ReadSignal<MercadoState>) -> impl IntoView {
-    let (prediction, set_prediction) = create_signal(String::from("This works"));
-    let (end, set_end) = create_signal(time::OffsetDateTime::now_utc());
+    let step = create_rw_signal(0);
+    let (prediction, set_prediction) = create_signal(String::new());
+    let end_date = create_rw_signal(time::OffsetDateTime::now_utc());
+    let (end_time, set_end_time) = create_signal(String::from("12:00"));
     let (judge_count, set_judge_count) = create_signal(3.0);
     let (judges, set_judges) = create_signal::<Vec<UserPubKey>>(vec![]);
-    let (new_judge, set_new_judge) =
-        create_signal(generate_keypair(&mut rand::thread_rng()).1.to_string());
     let (decision, set_decision) = create_signal(3.0);
-    let (judge_share, set_judge_share) = create_signal(10000.0);
+    let decision_in_days = create_rw_signal(true);
+    let (judge_share, set_judge_share) = create_signal(1.0);
+    let example_pot = create_rw_signal::<Sats>(100_000);
+
+    let apply = move |draft: Draft| {
+        set_prediction.set(draft.prediction);
+        end_date.set(
+            time::OffsetDateTime::from_unix_timestamp(draft.trading_end.timestamp())
+                .unwrap_or_else(|_| time::OffsetDateTime::now_utc()),
+        );
+        set_end_time.set(draft.trading_end.format("%H:%M").to_string());
+        if draft.decision_period_sec % 86400 == 0 {
+            decision_in_days.set(true);
+            set_decision.set((draft.decision_period_sec / 86400) as f64);
+        } else {
+            decision_in_days.set(false);
+            set_decision.set((draft.decision_period_sec / 3600) as f64);
+        }
+        set_judge_share.set(draft.judge_share_ppm as f64 / 10_000.0);
+        set_judge_count.set(draft.judge_count as f64);
+        set_judges.set(draft.judges);
+    };
+
+    let query = use_query_map();
+    let query_value = move |key: &str| query.with_untracked(|query| query.get(key).cloned());
+    let draft = query_value("draft")
+        .and_then(|id| id.parse().ok())
+        .and_then(drafts::get);
+    let draft_id = draft
+        .as_ref()
+        .map(|draft| draft.id)
+        .unwrap_or_else(drafts::new_id);
+    if let Some(draft) = draft {
+        apply(draft);
+    }
+    let repropose = query_value("repropose").and_then(|id| id.parse::<RowId>().ok());
+    let repropose_error = create_rw_signal::<Option<String>>(None);
+    if let Some(id) = repropose {
+        spawn_local(async move {
+            match get_reproposal(id, state.get_untracked().access).await {
+                Ok((overview, remaining)) => apply(Draft::from_overview(&overview, remaining)),
+                Err(e) => repropose_error.set(Some(format!("Couldn't propose again: {}", e))),
+            }
+        });
+    }
+    if let Some(id) = query_value("duplicate").and_then(|id| id.parse::<RowId>().ok()) {
+        spawn_local(async move {
+            let copy = async {
+                let overview = get_prediction_overview(id).await?;
+                let judges = get_judges(Some(id), None).await?;
+                Ok::<_, String>(Draft::from_overview(
+                    &overview,
+                    judges.into_iter().map(|judge| judge.user).collect(),
+                ))
+            };
+            match copy.await {
+                Ok(copy) => apply(copy),
+                Err(e) => log::error!("couldn't load prediction {}: {}", id, e),
+            }
+        });
+    }
 
     let new_prediction_action =
         create_action(|request: &NewPredictionRequest| new_prediction(request.clone()));
-    let message = create_rw_signal(Some(view! {}.into_view()));
+    create_effect(move |_| {
+        if let Some(Ok(id)) = new_prediction_action.value().get() {
+            created::remember(id);
+            drafts::remove(draft_id);
+        }
+    });
     let new_prediction_id = create_local_resource(
         move || new_prediction_action.version().get(),
         move |_| fetch_rw_signal(new_prediction_action.value()),
     );
 
-    view! {
-        <Stack spacing=Size::Em(1.0)>
-            <H3>"Create a new prediction"</H3>
-            <Box style="width: 50%"><TextInput get=prediction set=set_prediction placeholder="Prediction" /></Box>
+    let trading_end = Signal::derive(move || {
+        let date = DateTime::from_timestamp(end_date.get().unix_timestamp(), 0)
+            .unwrap()
+            .date_naive();
+        let time = NaiveTime::parse_from_str(end_time.get().as_str(), "%H:%M").unwrap_or_default();
+        date.and_time(time).and_utc()
+    });
+    let decision_period_sec = Signal::derive(move || {
+        validation::decision_period_sec(decision.get(), decision_in_days.get())
+    });
+    let request = Signal::derive(move || NewPredictionRequest {
+        decision_period_sec: decision_period_sec.get().unwrap_or_default(),
+        judge_count: judge_count.get().max(0.0) as u32,
+        judge_share_ppm: (judge_share.get().max(0.0) * 10_000.0).round() as u32,
+        judges: judges.get(),
+        prediction: prediction.get().trim().to_string(),
+        trading_end: trading_end.get(),
+    });
+    create_effect(move |_| {
+        let draft = Draft::new(draft_id, &request.get());
+        let created = new_prediction_action
+            .value()
+            .with(|created| matches!(created, Some(Ok(_))));
+        if !created && !draft.is_empty() {
+            drafts::put(draft);
+        }
+    });
+    let errors = Signal::derive(move || {
+        let me = state.with(|state| state.access.as_ref().map(|access| access.user));
+        validation::validate(&request.get(), decision_period_sec.get(), Utc::now(), me)
+    });
+    let server_error = Signal::derive(move || match new_prediction_id.get().flatten() {
+        Some(Err(e)) => Some((validation::field_of(e.as_str()), e)),
+        _ => None,
+    });
+    create_effect(move |_| {
+        if let Some((Some(field), _)) = server_error.get() {
+            step.set(wizard_step(field));
+        }
+    });
+    let field_errors = move |field: Field| {
+        let mut messages = errors.with(|errors| validation::errors_for(errors, field));
+        if let Some((Some(other), e)) = server_error.get() {
+            if other == field {
+                messages.push(e);
+            }
+        }
+        messages
+            .into_iter()
+            .map(|message| view! {<small class="field-error">{message}</small><br/>})
+            .collect::<Vec<_>>()
+    };
+    let step_valid = move |step: usize| {
+        errors.with(|errors| errors.iter().all(|(field, _)| wizard_step(*field) != step))
+    };
+    let invalid = Signal::derive(move || {
+        new_prediction_action.pending().get() || !errors.with(|errors| errors.is_empty())
+    });
+    let expected_share = move || {
+        let share = request.get().judge_share_ppm as u128;
+        (example_pot.get() as u128 * share / 1_000_000) as i128
+    };
+
+    let question = move || {
+        view! {
+            <p>"Ask a question that can be answered with true or false once trading ends."</p>
+            <Box style="width: 50%">
+                <TextInput get=prediction set=set_prediction placeholder="Prediction" />
+                {move || field_errors(Field::Name)}
+            </Box>
+        }
+    };
+    let timing = move || {
+        view! {
             <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(2.0) >
                 <div>
-                    <DateSelector value=time::OffsetDateTime::now_utc() on_change=move |date: time::OffsetDateTime| {
-                        set_end.set(date)
+                    <DateSelector value=end_date.get_untracked() on_change=move |date: time::OffsetDateTime| {
+                        end_date.set(date)
                     }/>
-                    <label>"Ends at "{move || format!("{}", end.get())}</label>
+                    <label>"Trading ends at "
+                        <input type="time"
+                            prop:value=move || end_time.get()
+                            on:input=move |ev| set_end_time.set(event_target_value(&ev)) />
+                        " UTC"
+                    </label><br/>
+                    {move || field_errors(Field::TradingEnd)}
                 </div>
                 <div>
-                    <div>
-                        <NumberInput get=decision set=set_decision step=1.0 min=1.0 />
-                        <label>"Decision duration"</label>
-                    </div>
-                    <div>
-                        <NumberInput get=judge_share set=set_judge_share step=1000.0 min=0.0 />
-                        <label>"Portion for Judges (ppm)"</label>
-                    </div>
-                    <div>
-                        <NumberInput get=judge_count set=set_judge_count step=1.0 min=1.0 />
-                        <label>"How many judges need to participate?"</label>
-                    </div>
+                    <NumberInput get=decision set=set_decision step=1.0 min=1.0 />
+                    <select on:change=move |ev| decision_in_days.set(event_target_value(&ev) == "days")>
+                        <option value="hours" selected=move || !decision_in_days.get()>"hours"</option>
+                        <option value="days" selected=move || decision_in_days.get()>"days"</option>
+                    </select>
+                    <label>" for the judges to decide"</label><br/>
+                    {move || field_errors(Field::DecisionPeriod)}
                 </div>
             </Stack>
-            <Box style="width: 50%">"Judges: "
-                <TextInput get=new_judge set=set_new_judge placeholder="Judge Public Key" />
-            <Button on_click=move |_| {
-                if let Ok(judge) = new_judge.get().parse() {
-                    let mut judges = judges.get();
-                    judges.push(judge);
-                    set_judges.set(judges);
-                    set_new_judge.set(generate_keypair(&mut rand::thread_rng()).1.to_string());
-                } else {
-                    return
-                }
-            }>"Add"</Button></Box>
+        }
+    };
+    let judge_setup = move || {
+        view! {
+            <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(2.0) >
+                <div>
+                    <NumberInput get=judge_share set=set_judge_share step=0.1 min=0.0 max=100.0 />
+                    <label>"% of the pot for the judges ("{move || request.get().judge_share_ppm}" ppm)"</label><br/>
+                    {move || field_errors(Field::JudgeShare)}
+                    <small>"For a pot of "</small>
+                    <SatsInput value=example_pot state=state />
+                    <small>" the judges get "{move || view!{<SatsDisplay amount=expected_share() state=state />}}</small>
+                </div>
+                <div>
+                    <NumberInput get=judge_count set=set_judge_count step=1.0 min=1.0 />
+                    <label>"How many judges need to participate?"</label><br/>
+                    {move || field_errors(Field::JudgeCount)}
+                </div>
+            </Stack>
+            <JudgePicker judges=judges set_judges=set_judges state=state />
             <ul>
             <For each=move || judges.get() key=move |judge| judge.clone()
                 children=move |judge: UserPubKey| view!{
-                    <li><ShortenedString string=judge.to_string() />" "
+                    <li><Username user=Some(judge) />" "
                         <Button
                             on_click=move |_| {
                                 let mut judges = judges.get();
@@ -752,49 +2289,138 @@ pub fn NewPrediction(state: ReadSignal<MercadoState>) -> impl IntoView {
                     </li>
                 } />
             </ul>
+            {move || field_errors(Field::Judges)}
+        }
+    };
+    let review = move || {
+        let request = request.get();
+        let errors = errors.get();
+        view! {
+            <TableContainer><Table bordered=true>
+                <Tbody>
+                    <Tr><Th>"Prediction"</Th><Td>{request.prediction.clone()}</Td></Tr>
+                    <Tr><Th>"Trading end"</Th><Td>{request.trading_end.format("%Y-%m-%d %H:%M UTC").to_string()}</Td></Tr>
+                    <Tr><Th>"Decision period"</Th><Td>{format!(
+                        "{} hours ({} seconds)", request.decision_period_sec / 3600, request.decision_period_sec
+                    )}</Td></Tr>
+                    <Tr><Th>"Judge share"</Th><Td>{format!(
+                        "{}% ({} ppm)", request.judge_share_ppm as f64 / 10_000.0, request.judge_share_ppm
+                    )}</Td></Tr>
+                    <Tr><Th>"Judges needed"</Th><Td>{request.judge_count}</Td></Tr>
+                    <Tr><Th>"Judges"</Th><Td>{request.judges.iter().map(|judge| view!{
+                        <Username user=Some(*judge) /><br/>
+                    }).collect::<Vec<_>>()}</Td></Tr>
+                </Tbody>
+            </Table></TableContainer>
+            {errors.into_iter().map(|(field, error)| view!{
+                <small class="field-error">{error}" "
+                    <a href="#" on:click=move |ev| {
+                        ev.prevent_default();
+                        step.set(wizard_step(field));
+                    }>"Edit"</a>
+                </small><br/>
+            }).collect::<Vec<_>>()}
+        }
+    };
+
+    view! {
+        <Stack spacing=Size::Em(1.0)>
+            <H3>"Create a new prediction"</H3>
+            <small>"Your input is saved as a draft while you type. "<Link href="/drafts">"All drafts"</Link></small>
+            <Cond cond=repropose.is_some() view=view!{
+                <p>"Proposing again without the judges who refused. Add replacement judges and pick a new end date."</p>
+            } />
+            <p><strong>{move || repropose_error.get()}</strong></p>
+            <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(1.0)>
+                {WIZARD_STEPS.iter().enumerate().map(|(i, label)| view!{
+                    <span style=move || if step.get() == i {"font-weight: bold"} else {""}>
+                        {format!("{}. {}", i + 1, label)}
+                    </span>
+                }).collect::<Vec<_>>()}
+            </Stack>
+            {move || match step.get() {
+                0 => question().into_view(),
+                1 => timing().into_view(),
+                2 => judge_setup().into_view(),
+                _ => review().into_view(),
+            }}
             {
                 move || {
-                    if let Some(message) = message.get() {
-                        message
-                    } else {
-                        match new_prediction_id.get().flatten() {
-                            Some(Ok(rowid)) => {
-                                view!{<Redirect path={format!("/prediction/{}", rowid)} />}.into_view()
-                            }
-                            Some(Err(e)) => {
-                                view!{<label>{format!("{:?}", e)}</label>}.into_view()
-                            }
-                            None => {
-                                view!{}.into_view()
-                            }
+                    match new_prediction_id.get().flatten() {
+                        Some(Ok(rowid)) => {
+                            view!{<Redirect path={format!("/prediction/{}", rowid)} />}.into_view()
+                        }
+                        Some(Err(e)) if validation::field_of(e.as_str()).is_none() => {
+                            view!{<label>{format!("{:?}", e)}</label>}.into_view()
+                        }
+                        _ => {
+                            view!{}.into_view()
                         }
                     }
                 }
             }
-            <Button on_click=move |_| {
-                let result = move || {
-                    let request = NewPredictionRequest {
-                        decision_period_sec: decision.get() as u32 * 86400,
-                        judge_count: judge_count.get() as u32,
-                        judge_share_ppm: judge_share.get() as u32,
-                        judges: judges.get(),
-                        prediction: prediction.get(),
-                        trading_end: DateTime::from_timestamp(end.get().unix_timestamp(),0).unwrap(),
-                    };
-                    new_prediction_action.dispatch(request);
-                    Ok::<(),anyhow::Error>(())
-                };
-                match result() {
-                    Ok(_) => {
-                        message.set(None)
-                    }
-                    Err(e) => {
-                        message.set(Some(view!{
-                            <label>{format!("{}", e)}</label>
-                        }.into_view()));
+            <ButtonGroup>
+                <Button disabled=Signal::derive(move || step.get() == 0)
+                    on_click=move |_| step.update(|step| *step -= 1)>"Back"</Button>
+                {move || if step.get() + 1 < WIZARD_STEPS.len() {
+                    view!{
+                        <Button disabled=Signal::derive(move || !step_valid(step.get()))
+                            on_click=move |_| step.update(|step| *step += 1)>"Next"</Button>
+                    }.into_view()
+                } else {
+                    view!{
+                        <Button disabled=invalid on_click=move |_| {
+                            new_prediction_action.dispatch(request.get());
+                        } >"Create"</Button>
+                    }.into_view()
+                }}
+            </ButtonGroup>
+        </Stack>
+    }
+}
+#[component]
+pub fn Drafts() -> impl IntoView {
+    let saved = create_rw_signal(drafts::load());
+    view! {
+        <Stack spacing=Size::Em(1.0)>
+            <H3>"Drafts"</H3>
+            {move || saved.with(|saved| saved.is_empty()).then(|| view!{<p>"No drafts saved"</p>})}
+            <TableContainer><Table bordered=true hoverable=true>
+                <Thead><Tr>
+                    <Th>"Prediction"</Th>
+                    <Th>"Trading end"</Th>
+                    <Th>"Judges"</Th>
+                    <Th>"Saved"</Th>
+                    <Th>"Actions"</Th>
+                </Tr></Thead>
+                <Tbody><For each=move || {
+                    let mut sorted = saved.get();
+                    sorted.sort_by_key(|draft| std::cmp::Reverse(draft.saved));
+                    sorted
+                } key=|draft| (draft.id, draft.saved)
+                children=move |draft: Draft| {
+                    let id = draft.id;
+                    view!{
+                        <Tr>
+                            <Td>{match draft.prediction.trim() {
+                                "" => "Untitled".to_string(),
+                                title => title.to_string(),
+                            }}</Td>
+                            <Td>{draft.trading_end.format("%Y-%m-%d %H:%M").to_string()}</Td>
+                            <Td>{format!("{} of {}", draft.judge_count, draft.judges.len())}</Td>
+                            <Td>{draft.saved.format("%Y-%m-%d %H:%M").to_string()}</Td>
+                            <Td>
+                                <LinkButton href=format!("/new_prediction?draft={}", id)>"Resume"</LinkButton>
+                                <Button on_click=move |_| {
+                                    drafts::remove(id);
+                                    saved.set(drafts::load());
+                                }>"Delete"</Button>
+                            </Td>
+                        </Tr>
                     }
-                }
-            } >"Create"</Button>
+                } /></Tbody>
+            </Table></TableContainer>
+            <LinkButton href="/new_prediction">"New prediction"</LinkButton>
         </Stack>
     }
 }
@@ -819,26 +2445,89 @@ pub fn AddBet(state: ReadSignal<MercadoState>) -> impl IntoView {
     } else {
         create_signal::<Option<PredictionOverviewResponse>>(None)
     };
-    let (bet, set_bet) = create_signal(false);
-    let (amount, set_amount) = create_signal(100.0);
+    let query_value = move |key: &str| query.with_untracked(|query| query.get(key).cloned());
+    let (bet, set_bet) = create_signal(
+        query_value("bet")
+            .and_then(|bet| bet.parse().ok())
+            .unwrap_or(false),
+    );
+    let amount = create_rw_signal::<Sats>(
+        query_value("amount")
+            .and_then(|amount| amount.parse().ok())
+            .unwrap_or(100),
+    );
+    let amount_valid = create_rw_signal(true);
     let message = create_rw_signal(None);
 
     let create_new_bet = create_action(|(request, access): &(AddBetRequest, AccessRequest)| {
         add_bet(request.clone(), access.clone())
     });
+    let available = create_local_resource(
+        move || (state.get().access, create_new_bet.version().get()),
+        move |(access, _)| async move {
+            match access {
+                Some(access) => get_available_balance(access.user, access).await.map(Some),
+                None => Ok(None),
+            }
+        },
+    );
+    let shortfall = create_rw_signal(None::<(Sats, AddBetRequest, AccessRequest)>);
+    let deposit = create_rw_signal(None::<(RowId, Invoice)>);
+    let fund_and_bet = create_action(
+        move |(shortfall, request, access): &(Sats, AddBetRequest, AccessRequest)| {
+            let (shortfall, request, access) = (*shortfall, request.clone(), access.clone());
+            async move {
+                let deposit_request = DepositRequest {
+                    amount: shortfall,
+                    user: access.user,
+                };
+                let (id, invoice) = make_deposit_bolt11(deposit_request, access.clone()).await?;
+                deposit.set(Some((id, invoice)));
+                let settled = wait_for_deposit(id, access.clone()).await;
+                deposit.set(None);
+                settled?;
+                add_bet(request, access).await
+            }
+        },
+    );
+    // An offer to cover the shortfall is only valid for the inputs it was
+    // made for, unless the deposit for it is already underway.
+    create_effect(move |_| {
+        amount.track();
+        bet.track();
+        prediction.track();
+        if !fund_and_bet.pending().get_untracked() {
+            shortfall.set(None);
+        }
+    });
     let add_bet = move || {
         let access = if let Some(access) = state.get().access {
             access
         } else {
             bail!("Not logged in")
         };
+        if !amount_valid.get() {
+            bail!("Invalid amount")
+        }
         let request = AddBetRequest {
-            prediction: prediction.get().unwrap().id,
+            prediction: prediction.get().context("Select a prediction")?.id,
             bet: bet.get(),
             user: access.user,
-            amount: amount.get() as Sats,
+            amount: amount.get(),
         };
-        create_new_bet.dispatch((request, access));
+        let available = available
+            .get()
+            .and_then(|available| available.ok())
+            .flatten();
+        match available {
+            Some(available) if request.amount > available => {
+                shortfall.set(Some((request.amount - available, request, access)));
+            }
+            _ => {
+                shortfall.set(None);
+                create_new_bet.dispatch((request, access));
+            }
+        }
         Ok(())
     };
     let created_bet = create_local_resource(
@@ -878,9 +2567,42 @@ pub fn AddBet(state: ReadSignal<MercadoState>) -> impl IntoView {
                 <p>{move || format!("Bet: {}", bet.get())}</p>
             </div>
             <div>
-                <NumberInput get=amount set=set_amount step=100.0 min=1.0 />
-                <label>"Amount (sats)"</label>
+                <label>"Amount"</label>
+                <SatsInput value=amount valid=amount_valid state=state min=1 />
+                {move || view!{<SatsDisplay amount=amount.get() state=state />}}<br/>
+                <UnwrapResource resource=available view=move |available| available.map(|available| view!{
+                    <small>"Available: "<SatsDisplay amount=available state=state /></small>
+                }) />
             </div>
+            {move || shortfall.get().map(|(missing, request, access)| {
+                let prediction_id = request.prediction;
+                view!{
+                <div class="bet-slip">
+                    <p>"Your available balance is short by "<SatsDisplay amount=missing state=state />"."</p>
+                    {move || match deposit.get() {
+                        Some((id, invoice)) => view!{
+                            <p><small>"Pay this invoice to cover the difference. The bet is placed once it settles:"</small><br/>
+                                <code>{invoice.clone()}</code></p>
+                            <WeblnPay invoice=invoice tx=Some(id) state=state />
+                        }.into_view(),
+                        None => view!{}.into_view(),
+                    }}
+                    <Button
+                        disabled=fund_and_bet.pending()
+                        on_click=move |_| fund_and_bet.dispatch((missing, request.clone(), access.clone()))
+                    >"Deposit the difference and place the bet"</Button>
+                    {move || match fund_and_bet.value().get() {
+                        Some(Ok(payment)) => view!{
+                            <p>
+                                <BetPayment invoice=payment.to_string() state=state />" "
+                                <Link href=format!("/prediction/{}", prediction_id)>"Go to prediction"</Link>
+                            </p>
+                        }.into_view(),
+                        Some(Err(e)) => view!{<small>{format!("{:?}", e)}</small>}.into_view(),
+                        None => view!{}.into_view(),
+                    }}
+                </div>
+            }})}
             <label><small>
             {
                 move || {
@@ -888,10 +2610,10 @@ pub fn AddBet(state: ReadSignal<MercadoState>) -> impl IntoView {
                         message
                     } else {
                         match created_bet.get().flatten() {
-                            Some(Ok(payment)) => {
-                                //TODO redirect to bet status page to enable paying
-                                view!{<Redirect path={format!("/prediction/{}", prediction.get().unwrap().id)} />}.into_view()
-                            }
+                            Some(Ok(payment)) => view!{
+                                <BetPayment invoice=payment.to_string() state=state />" "
+                                <Link href=format!("/prediction/{}", prediction.get().unwrap().id)>"Go to prediction"</Link>
+                            }.into_view(),
                             Some(Err(e)) => {
                                 format!("{:?}", e).into_view()
                             }
@@ -912,6 +2634,156 @@ pub fn AddBet(state: ReadSignal<MercadoState>) -> impl IntoView {
     }
 }
 #[component]
+pub fn Basket(state: ReadSignal<MercadoState>) -> impl IntoView {
+    let access = if let Some(access) = state.get_untracked().access {
+        create_rw_signal(access)
+    } else {
+        return view! {<p><Link href="/login">"Login"</Link>" to place the bets in your basket"</p>}
+            .into_view();
+    };
+    let items = create_rw_signal(basket::load());
+    create_effect(move |_| items.with(|items| basket::save(items)));
+    let predictions = create_local_resource(
+        move || items.with(|items| items.iter().map(|item| item.prediction).collect::<Vec<_>>()),
+        move |ids| async move {
+            let mut predictions = vec![];
+            for id in ids {
+                predictions.push(get_prediction_overview(id).await?);
+            }
+            Ok::<_, String>(predictions)
+        },
+    );
+    let available = create_local_resource(
+        move || access.get(),
+        move |access| get_available_balance(access.user, access),
+    );
+    let failures = create_rw_signal(Vec::<(i64, String)>::new());
+    let done = create_rw_signal(0);
+    let total = create_rw_signal(0);
+    let submit = create_action(move |_: &()| {
+        let pending = items.get_untracked();
+        done.set(0);
+        total.set(pending.len());
+        async move {
+            let results =
+                place_bets(pending, access.get_untracked(), |handled| done.set(handled)).await;
+            let mut failed = vec![];
+            let mut placed = vec![];
+            for (item, result) in results {
+                match result {
+                    Ok(payment) => placed.push((item, payment)),
+                    Err(e) => failed.push((item.id, e)),
+                }
+            }
+            items.update(|items| {
+                items.retain(|item| !placed.iter().any(|(placed, _)| placed.id == item.id))
+            });
+            failures.set(failed);
+            placed
+        }
+    });
+    create_effect(move |_| {
+        if submit.version().get() > 0 {
+            available.refetch();
+        }
+    });
+
+    view! {
+        <Stack spacing=Size::Em(1.0)>
+            <H3>"Bet basket"</H3>
+            <UnwrapResource resource=predictions view=move |predictions| {
+                let items = items.get();
+                if items.is_empty() {
+                    return view!{<p>"The basket is empty. Add bets from the bet slip on a prediction."</p>}.into_view();
+                }
+                let stake: i128 = items.iter().map(|item| item.amount as i128).sum();
+                view!{
+                    <TableContainer><Table bordered=true hoverable=true>
+                        <Thead><Tr>
+                            <Th>"Prediction"</Th>
+                            <Th>"Bet"</Th>
+                            <Th>"Amount"</Th>
+                            <Th>"Pays if right"</Th>
+                            <Th>"Status"</Th>
+                            <Th></Th>
+                        </Tr></Thead>
+                        <Tbody>{items.into_iter().map(|item| {
+                            let prediction = predictions.iter().find(|prediction| prediction.id == item.prediction);
+                            let projected = prediction.map(|prediction| {
+                                let ratio = if item.bet {
+                                    (prediction.ratio.0 + item.amount, prediction.ratio.1)
+                                } else {
+                                    (prediction.ratio.0, prediction.ratio.1 + item.amount)
+                                };
+                                payout(item.amount, item.bet, ratio, prediction.judge_share_ppm)
+                            });
+                            let closed = prediction.is_some_and(|prediction| prediction.state != MarketState::Trading);
+                            let id = item.id;
+                            view!{
+                                <Tr>
+                                    <Td><Link href=format!("/prediction/{}", item.prediction)>{item.name.clone()}</Link></Td>
+                                    <Td>{item.bet.to_string()}</Td>
+                                    <Td><SatsDisplay amount=item.amount state=state /></Td>
+                                    <Td>{projected.map(|projected| view!{<SatsDisplay amount=projected state=state />})}</Td>
+                                    <Td>
+                                        <Cond cond=closed view=view!{<strong>"⚠ No longer trading"</strong>} />
+                                        {move || failures.with(|failures| failures
+                                            .iter()
+                                            .find(|(failed, _)| *failed == id)
+                                            .map(|(_, e)| view!{<small>{format!("Failed: {:?}", e)}</small>}))}
+                                    </Td>
+                                    <Td><Button on_click=move |_| {
+                                        items.update(|items| items.retain(|item| item.id != id));
+                                    }>"Remove"</Button></Td>
+                                </Tr>
+                            }
+                        }).collect::<Vec<_>>()}</Tbody>
+                    </Table></TableContainer>
+                    <p>
+                        "Total stake: "<SatsDisplay amount=stake state=state /><br/>
+                        <UnwrapResource resource=available view=move |available| {
+                            let short = stake - available as i128;
+                            view!{
+                                "Available: "<SatsDisplay amount=available state=state />" "
+                                {(short > 0).then(|| view!{
+                                    <strong>"⚠ Short by "<SatsDisplay amount=short state=state /></strong>
+                                })}
+                            }
+                        } />
+                    </p>
+                }.into_view()
+            } />
+            {move || submit.pending().get().then(|| view!{<BatchProgress done=done total=total verb="placed" />})}
+            {move || submit.value().get().map(|placed| view!{
+                <p>
+                    {format!("{} bet(s) placed", placed.len())}
+                    {move || {
+                        let failed = failures.with(|failures| failures.len());
+                        (failed > 0).then(|| format!(", {} failed and stay in the basket", failed))
+                    }}
+                </p>
+                {placed.into_iter().map(|(item, payment)| view!{
+                    <p>
+                        <strong>{format!("Bet {} on ", item.bet)}{item.name}</strong><br/>
+                        <BetPayment invoice=payment.to_string() state=state />
+                    </p>
+                }).collect::<Vec<_>>()}
+            })}
+            <ButtonGroup>
+                <Button
+                    disabled=Signal::derive(move || submit.pending().get() || items.with(|items| items.is_empty()))
+                    on_click=move |_| submit.dispatch(())
+                >{move || if failures.with(|failures| failures.is_empty()) {"Place all bets"} else {"Retry"}}</Button>
+                <Button on_click=move |_| {
+                    items.set(vec![]);
+                    failures.set(vec![]);
+                }>"Empty basket"</Button>
+            </ButtonGroup>
+        </Stack>
+    }
+    .into_view()
+}
+#[component]
 pub fn Wallet(state: ReadSignal<MercadoState>) -> impl IntoView {
     let access = if let Some(access) = state.get_untracked().access {
         create_rw_signal(access)
@@ -931,25 +2803,9 @@ pub fn Wallet(state: ReadSignal<MercadoState>) -> impl IntoView {
         move || (user, access.get()),
         move |(user, a)| get_balances_for_user(user, a),
     );
-    let deposits = create_local_resource(
-        move || (user, access.get()),
-        move |(user, a)| {
-            let request = TxsRequest {
-                user: Some(user),
-                direction: Some(TxDirection::Deposit),
-            };
-            get_txs(request, a)
-        },
-    );
-    let withdrawals = create_local_resource(
+    let ledger = create_local_resource(
         move || (user, access.get()),
-        move |(user, a)| {
-            let request = TxsRequest {
-                user: Some(user),
-                direction: Some(TxDirection::Withdrawal),
-            };
-            get_txs(request, a)
-        },
+        move |(user, a)| get_ledger(user, a),
     );
 
     view! {
@@ -957,91 +2813,315 @@ pub fn Wallet(state: ReadSignal<MercadoState>) -> impl IntoView {
             <H3>"Bitcoin Wallet"</H3>
             <UnwrapResourceFor state=state resource=balances view=move |balances| view!{
                 <p>
-                    "Available Balance: "{balances.0}" sats"<br/>
-                    "Total Balance: "{balances.1}" sats"<br/>
+                    "Available Balance: "<SatsDisplay amount=balances.0 state=state /><br/>
+                    "Total Balance: "<SatsDisplay amount=balances.1 state=state /><br/>
                 </p>
             } />
-            <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(2.0)>
-                <div>
-                    <LinkButton href="/make_deposit">"Make Deposit"</LinkButton>
-                    <UnwrapResourceForUser user=user state=state resource=deposits view=move |deposits| view!{
-                        <TableContainer><Table bordered=true hoverable=true>
-                            <Thead><tr>
-                                <th>"Invoice"</th>
-                                <th>"State"</th>
-                            </tr></Thead>
-                            <Tbody><For each=move || deposits.clone() key=|id| id.clone() children=move |id: RowId| view!{
-                                <DepositListItem state=state id=id />
-                            } /></Tbody>
-                        </Table></TableContainer>
-                    } />
-                </div>
-                <div>
-                    <LinkButton href="/make_withdrawal">"Make Withdrawal"</LinkButton>
-                    <UnwrapResourceForUser user=user state=state resource=withdrawals view=move |withdrawals| view!{
+            <ButtonGroup>
+                <LinkButton href=format!("/make_deposit/{}", user)>"Make Deposit"</LinkButton>
+                <LinkButton href=format!("/make_withdrawal/{}", user)>"Make Withdrawal"</LinkButton>
+            </ButtonGroup>
+            <UnwrapResourceForUser user=user state=state resource=ledger view=move |ledger| view!{
+                <BalanceBreakdown user=user ledger=ledger.clone() state=state />
+                <BalanceChart user=user ledger=ledger.clone() state=state />
+                <HistoryExport kind=ExportKind::Transactions user=Some(user) state=state />
+                <TxTimeline ledger=ledger state=state />
+            } />
+        </Stack>
+    }
+    .into_view()
+}
+#[component]
+pub fn BalanceBreakdown(
+    user: UserPubKey,
+    ledger: Vec<LedgerEntry>,
+    state: ReadSignal<MercadoState>,
+) -> impl IntoView {
+    let access = if let Some(access) = state.get_untracked().access {
+        create_rw_signal(access)
+    } else {
+        return view! {}.into_view();
+    };
+    let breakdown = create_local_resource(
+        move || (user, access.get()),
+        move |(user, a)| get_balance_breakdown(user, ledger.clone(), a),
+    );
+
+    view! {
+        <UnwrapResourceForUser user=user state=state resource=breakdown view=move |breakdown| {
+            let unexplained = breakdown.unexplained();
+            view!{
+                <Collapsible>
+                    <CollapsibleHeader slot>"Locked: "<SatsDisplay amount=breakdown.locked() state=state /></CollapsibleHeader>
+                    <CollapsibleBody slot>
                         <TableContainer><Table bordered=true hoverable=true>
-                            <Thead><tr>
-                                <th>"Payment hash"</th>
-                                <th>"State"</th>
-                            </tr></Thead>
-                            <Tbody><For each=move || withdrawals.clone() key=|id| id.clone() children=move |id: RowId| view!{
-                                <WithdrawListItem state=state id=id />
-                            } /></Tbody>
+                            <Thead><Tr>
+                                <Th>"Locked by"</Th>
+                                <Th>"Amount"</Th>
+                            </Tr></Thead>
+                            <Tbody>
+                                {breakdown.bets.iter().map(|(bet, prediction)| view!{
+                                    <Tr>
+                                        <Td>"Bet "{bet.bet}" on "<Link href=format!("/prediction/{}", prediction.id)>{prediction.name.clone()}</Link>
+                                            " ("{prediction.state.to_string()}")"</Td>
+                                        <Td><SatsDisplay amount=bet.amount state=state /></Td>
+                                    </Tr>
+                                }).collect::<Vec<_>>()}
+                                {breakdown.withdrawals.iter().map(|entry| view!{
+                                    <Tr>
+                                        <Td>"Pending "<Link href=format!("/tx/{}", entry.id)>"withdrawal"</Link></Td>
+                                        <Td><SatsDisplay amount=entry.tx.amount state=state /></Td>
+                                    </Tr>
+                                }).collect::<Vec<_>>()}
+                                <Cond cond={unexplained != 0} view=view!{
+                                    <Tr>
+                                        <Td><strong>"Unexplained remainder"</strong></Td>
+                                        <Td><strong><SatsDisplay amount=unexplained state=state /></strong></Td>
+                                    </Tr>
+                                } />
+                            </Tbody>
                         </Table></TableContainer>
-                    } />
-                </div>
-            </Stack>
-        </Stack>
+                    </CollapsibleBody>
+                </Collapsible>
+            }
+        } />
     }
     .into_view()
 }
 #[component]
-pub fn DepositListItem(state: ReadSignal<MercadoState>, id: RowId) -> impl IntoView {
+pub fn BalanceChart(
+    user: UserPubKey,
+    ledger: Vec<LedgerEntry>,
+    state: ReadSignal<MercadoState>,
+) -> impl IntoView {
     let access = if let Some(access) = state.get_untracked().access {
         create_rw_signal(access)
     } else {
         return view! {}.into_view();
     };
-    let tx = create_local_resource(move || id, move |id| get_tx(id, access.get()));
+    let history = create_local_resource(
+        move || (user, access.get()),
+        move |(user, a)| get_balance_history(user, ledger.clone(), a),
+    );
+    let days = create_rw_signal::<Option<i64>>(None);
 
     view! {
-        <tr>
-            <UnwrapResourceFor state=state resource=tx view=move |tx| {
-                match tx.tx_type {
-                    TxType::Bolt11 {details, state} => {
+        <UnwrapResourceForUser user=user state=state resource=history view=move |history| {
+            let series = move || {
+                let available = history.iter().map(|point| (point.time, point.available)).collect::<Vec<_>>();
+                let total = history.iter().map(|point| (point.time, point.total)).collect::<Vec<_>>();
+                let (available, total) = if let Some(days) = days.get() {
+                    let since = Utc::now() - Duration::days(days);
+                    (chart::since(&available, since), chart::since(&total, since))
+                } else {
+                    (available, total)
+                };
+                vec![
+                    Series {label: "Available".to_string(), color: "#e66956", points: available},
+                    Series {label: "Total".to_string(), color: "#4e9af1", points: total},
+                ]
+            };
+            view!{
+                <Collapsible>
+                    <CollapsibleHeader slot>"Balance history"</CollapsibleHeader>
+                    <CollapsibleBody slot>
+                        <ButtonGroup>
+                            <Button on_click=move |_| days.set(Some(7))>"7 days"</Button>
+                            <Button on_click=move |_| days.set(Some(30))>"30 days"</Button>
+                            <Button on_click=move |_| days.set(Some(90))>"90 days"</Button>
+                            <Button on_click=move |_| days.set(None)>"All"</Button>
+                        </ButtonGroup>
+                        {move || view!{<LineChart series=series() state=state />}}
+                    </CollapsibleBody>
+                </Collapsible>
+            }
+        } />
+    }
+    .into_view()
+}
+#[component]
+pub fn LineChart(series: Vec<Series>, state: ReadSignal<MercadoState>) -> impl IntoView {
+    let scale = if let Some(scale) = chart::Scale::fit(&series) {
+        scale
+    } else {
+        return view! {<p><small>"No data for this range"</small></p>}.into_view();
+    };
+    let hovered = create_rw_signal::<Option<String>>(None);
+    let unit = move || state.get().unit;
+    let label_x = format!("{:.1}", chart::PADDING - 5.0);
+    let bottom = format!("{:.1}", chart::HEIGHT - chart::PADDING);
+
+    view! {
+        <div>
+            <svg viewBox=format!("0 0 {} {}", chart::WIDTH, chart::HEIGHT) style="width: 100%; max-width: 800px">
+                <line x1=chart::PADDING.to_string() y1=bottom.clone()
+                    x2=(chart::WIDTH - chart::PADDING).to_string() y2=bottom.clone()
+                    stroke="gray" />
+                <text x=label_x.clone() y=format!("{:.1}", scale.y(scale.max)) text-anchor="end" font-size="10" fill="gray">{move || sats::format(scale.max, unit())}</text>
+                <text x=label_x y=format!("{:.1}", scale.y(scale.min)) text-anchor="end" font-size="10" fill="gray">{move || sats::format(scale.min, unit())}</text>
+                <text x=chart::PADDING.to_string() y=format!("{:.1}", chart::HEIGHT - chart::PADDING / 2.0) font-size="10" fill="gray">
+                    {scale.start.format("%Y-%m-%d").to_string()}</text>
+                <text x=(chart::WIDTH - chart::PADDING).to_string() y=format!("{:.1}", chart::HEIGHT - chart::PADDING / 2.0)
+                    text-anchor="end" font-size="10" fill="gray">
+                    {scale.end.format("%Y-%m-%d").to_string()}</text>
+                {series.iter().map(|series| view!{
+                    <polyline points=scale.step_line(&series.points) fill="none" stroke=series.color stroke-width="2" />
+                }).collect::<Vec<_>>()}
+                {series.iter().flat_map(|series| {
+                    let label = series.label.clone();
+                    series.points.iter().map(move |(time, value)| {
+                        let tooltip = format!("{} {}", time.format("%Y-%m-%d %H:%M"), label);
+                        let value = *value;
                         view!{
-                            <td><ShortenedString string={details.payment_request}/></td>
-                            <td>{format!("{:?}", state)}</td>
+                            <circle cx=format!("{:.1}", scale.x(*time)) cy=format!("{:.1}", scale.y(value)) r="4"
+                                fill=series.color
+                                on:mouseenter=move |_| hovered.set(Some(format!("{}: {}", tooltip, sats::format(value, unit()))))
+                                on:mouseleave=move |_| hovered.set(None) />
                         }
-                    }
+                    })
+                }).collect::<Vec<_>>()}
+            </svg>
+            <p><small>
+                {series.iter().map(|series| view!{
+                    <span style=format!("color: {}", series.color)>"● "{series.label.clone()}" "</span>
+                }).collect::<Vec<_>>()}
+                {move || hovered.get()}
+            </small></p>
+        </div>
+    }
+    .into_view()
+}
+#[component]
+pub fn TxTimeline(ledger: Vec<LedgerEntry>, state: ReadSignal<MercadoState>) -> impl IntoView {
+    let page_size = 20;
+    let direction = create_rw_signal::<Option<TxDirection>>(None);
+    let settlement = create_rw_signal::<Option<Settlement>>(None);
+    let page = create_rw_signal(0usize);
+
+    let filtered = Signal::derive(move || {
+        ledger
+            .iter()
+            .filter(|entry| direction.get().map_or(true, |d| d == entry.direction))
+            .filter(|entry| {
+                settlement
+                    .get()
+                    .map_or(true, |s| s == Settlement::of(&entry.tx))
+            })
+            .cloned()
+            .collect::<Vec<_>>()
+    });
+    let pages = move || (filtered.get().len() + page_size - 1) / page_size;
+
+    view! {
+        <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(1.0)>
+            <ButtonGroup>
+                <Button on_click=move |_| {direction.set(None); page.set(0)}>"All"</Button>
+                <Button on_click=move |_| {direction.set(Some(TxDirection::Deposit)); page.set(0)}>"Deposits"</Button>
+                <Button on_click=move |_| {direction.set(Some(TxDirection::Withdrawal)); page.set(0)}>"Withdrawals"</Button>
+            </ButtonGroup>
+            <ButtonGroup>
+                <Button on_click=move |_| {settlement.set(None); page.set(0)}>"Any state"</Button>
+                <Button on_click=move |_| {settlement.set(Some(Settlement::Pending)); page.set(0)}>"Pending"</Button>
+                <Button on_click=move |_| {settlement.set(Some(Settlement::Settled)); page.set(0)}>"Settled"</Button>
+                <Button on_click=move |_| {settlement.set(Some(Settlement::Failed)); page.set(0)}>"Failed"</Button>
+            </ButtonGroup>
+        </Stack>
+        <TableContainer><Table bordered=true hoverable=true>
+            <Thead><Tr>
+                <Th>"Date"</Th>
+                <Th>"Direction"</Th>
+                <Th>"Amount"</Th>
+                <Th>"State"</Th>
+                <Th>"Details"</Th>
+            </Tr></Thead>
+            <Tbody><For
+                each=move || filtered.get().into_iter().skip(page.get() * page_size).take(page_size)
+                key=|entry| entry.id
+                children=move |entry: LedgerEntry| view!{
+                    <TxTimelineItem entry=entry state=state />
                 }
-            } />
-        </tr>
+            /></Tbody>
+        </Table></TableContainer>
+        <p>
+            <Button disabled=Signal::derive(move || page.get() == 0)
+                on_click=move |_| page.set(page.get() - 1)>"Previous"</Button>
+            " Page "{move || page.get() + 1}" of "{move || pages().max(1)}" "
+            <Button disabled=Signal::derive(move || page.get() + 1 >= pages())
+                on_click=move |_| page.set(page.get() + 1)>"Next"</Button>
+        </p>
     }
     .into_view()
 }
 #[component]
-pub fn WithdrawListItem(state: ReadSignal<MercadoState>, id: RowId) -> impl IntoView {
+pub fn TxTimelineItem(entry: LedgerEntry, state: ReadSignal<MercadoState>) -> impl IntoView {
+    let (badge, reference) = match (&entry.direction, &entry.tx.tx_type) {
+        (TxDirection::Deposit, TxType::Bolt11 { details, .. }) => {
+            ("deposit", details.payment_request.clone())
+        }
+        (_, TxType::Bolt11 { details, .. }) => ("withdrawal", details.payment_hash.clone()),
+    };
+    view! {
+        <Tr>
+            <Td>{entry.tx.created.format("%Y-%m-%d %H:%M").to_string()}</Td>
+            <Td><span class=format!("badge {}", badge)>{badge}</span></Td>
+            <Td><SatsDisplay amount=entry.tx.amount state=state /></Td>
+            <Td>{Settlement::of(&entry.tx).to_string()}</Td>
+            <Td>
+                <ShortenedString string=reference />" "
+                <Link href=format!("/tx/{}", entry.id)>"Details"</Link>
+            </Td>
+        </Tr>
+    }
+}
+#[component]
+pub fn TxDetail(state: ReadSignal<MercadoState>) -> impl IntoView {
     let access = if let Some(access) = state.get_untracked().access {
         create_rw_signal(access)
     } else {
         return view! {}.into_view();
     };
+    let params = use_params_map();
+    let id = params.with_untracked(|p| p.get("id").cloned());
+    let id = if let Ok(id) = id.unwrap_or_default().parse::<RowId>() {
+        id
+    } else {
+        return view! {<p>"Transaction not found"</p>}.into_view();
+    };
     let tx = create_local_resource(move || id, move |id| get_tx(id, access.get()));
 
     view! {
-        <tr>
+        <Stack spacing=Size::Em(1.0)>
+            <H3>"Transaction "{id}</H3>
             <UnwrapResourceFor state=state resource=tx view=move |tx| {
+                let settlement = Settlement::of(&tx);
                 match tx.tx_type {
-                    TxType::Bolt11 {details, state} => {
+                    TxType::Bolt11 {details, state: tx_state} => {
+                        let fields = match serde_json::to_value(&details) {
+                            Ok(serde_json::Value::Object(fields)) => fields.into_iter().collect::<Vec<_>>(),
+                            _ => vec![],
+                        };
                         view!{
-                            <td><ShortenedString string={details.payment_hash}/></td>
-                            <td>{format!("{:?}", state)}</td>
+                            <TableContainer><Table bordered=true>
+                                <Tbody>
+                                    <Tr><Th>"Type"</Th><Td>"Bolt11"</Td></Tr>
+                                    <Tr><Th>"Amount"</Th><Td><SatsDisplay amount=tx.amount state=state /></Td></Tr>
+                                    <Tr><Th>"Created"</Th><Td>{tx.created.to_string()}</Td></Tr>
+                                    <Tr><Th>"State"</Th><Td>{settlement.to_string()}" ("{format!("{:?}", tx_state)}")"</Td></Tr>
+                                    {fields.into_iter().map(|(name, value)| view!{
+                                        <Tr>
+                                            <Th>{name}</Th>
+                                            <Td><small style="word-break: break-all">{
+                                                value.as_str().map(|value| value.to_string()).unwrap_or(value.to_string())
+                                            }</small></Td>
+                                        </Tr>
+                                    }).collect::<Vec<_>>()}
+                                </Tbody>
+                            </Table></TableContainer>
                         }
                     }
                 }
             } />
-        </tr>
+        </Stack>
     }
     .into_view()
 }
@@ -1058,7 +3138,8 @@ pub fn MakeDeposit(state: ReadSignal<MercadoState>) -> impl IntoView {
         .unwrap_or_default()
         .parse::<UserPubKey>()
         .unwrap_or(access.user);
-    let amount = create_rw_signal(1000.0);
+    let amount = create_rw_signal::<Sats>(1000);
+    let amount_valid = create_rw_signal(true);
     let tx_type = create_rw_signal(String::from("bolt11"));
 
     let make_new_deposit = create_action(|(request, access): &(DepositRequest, AccessRequest)| {
@@ -1070,8 +3151,11 @@ pub fn MakeDeposit(state: ReadSignal<MercadoState>) -> impl IntoView {
         } else {
             bail!("Not logged in")
         };
+        if !amount_valid.get() {
+            bail!("Invalid amount")
+        }
         let request = DepositRequest {
-            amount: amount.get() as Sats,
+            amount: amount.get(),
             user,
         };
         make_new_deposit.dispatch((request, access));
@@ -1087,8 +3171,8 @@ pub fn MakeDeposit(state: ReadSignal<MercadoState>) -> impl IntoView {
         <Stack spacing=Size::Em(1.0)>
             <h3>"Make Deposit"</h3>
             <div>
-                <NumberInput get=amount set=amount.write_only() step=1000.0 min=1.0 />
                 <label>"Amount"</label>
+                <SatsInput value=amount valid=amount_valid state=state min=1 />
             </div>
             <p>{
                 move || {
@@ -1096,9 +3180,11 @@ pub fn MakeDeposit(state: ReadSignal<MercadoState>) -> impl IntoView {
                         message
                     } else {
                         match created_deposit.get().flatten() {
-                            Some(Ok((_id, invoice))) => {
-                                //TODO redirect to bet status page to enable paying
-                                invoice.into_view()
+                            Some(Ok((id, invoice))) => {
+                                view!{
+                                    {invoice.clone()}<br/>
+                                    <WeblnPay invoice=invoice tx=Some(id) state=state />
+                                }.into_view()
                             }
                             Some(Err(e)) => {
                                 format!("{:?}", e).into_view()
@@ -1133,28 +3219,128 @@ pub fn MakeWithdrawal(state: ReadSignal<MercadoState>) -> impl IntoView {
         .unwrap_or_default()
         .parse::<UserPubKey>()
         .unwrap_or(access.user);
-    let amount = create_rw_signal(1000.0);
+    let me = access.user;
+    let amount = create_rw_signal::<Sats>(1000);
+    let amount_valid = create_rw_signal(true);
     let invoice = create_rw_signal(String::from(""));
     let tx_type = create_rw_signal(String::from("bolt11"));
 
-    let make_new_withdrawal =
-        create_action(|(request, access): &(WithdrawalRequest, AccessRequest)| {
-            make_withdrawal_bolt11(request.clone(), access.clone())
-        });
+    // Either uses the session or re-authenticates with a (challenge, signature) first
+    let make_new_withdrawal = create_action(
+        move |(request, reauth): &(WithdrawalRequest, Option<(String, String)>)| {
+            let (request, reauth) = (request.clone(), reauth.clone());
+            let access = state.get_untracked().access;
+            async move {
+                let access = match (reauth, access) {
+                    (Some((challenge, signature)), Some(_)) => {
+                        reauthenticate(me, signature, challenge).await?
+                    }
+                    (None, Some(access)) => access,
+                    (_, None) => return Err("Not logged in".to_string()),
+                };
+                make_withdrawal_bolt11(request, access).await
+            }
+        },
+    );
+    let limits_context = create_local_resource(
+        move || make_new_withdrawal.version().get(),
+        move |_| get_withdrawal_context(user, access.clone()),
+    );
+    // The checked request is kept so the confirmed withdrawal is exactly the
+    // one the limits were checked against.
+    let confirmation = create_rw_signal::<Option<(String, WithdrawalRequest)>>(None);
+    let challenge = create_action(move |_: &()| create_login_challenge(me.to_string()));
+    let (signature, set_signature) = create_signal(String::new());
+    let request = move || WithdrawalRequest {
+        amount: amount.get(),
+        invoice: invoice.get(),
+        user,
+    };
     let add_bet = move || {
-        let access = if let Some(access) = state.get().access {
-            access
-        } else {
+        let state = state.get();
+        if state.access.is_none() {
             bail!("Not logged in")
+        }
+        if !amount_valid.get() {
+            bail!("Invalid amount")
+        }
+        let (available, withdrawn) = match limits_context.get() {
+            Some(Ok(context)) => context,
+            Some(Err(e)) => bail!("Couldn't check withdrawal limits: {}", e),
+            None => bail!("Still checking withdrawal limits, try again in a moment"),
         };
-        let request = WithdrawalRequest {
-            amount: amount.get() as Sats,
-            invoice: invoice.get(),
-            user,
-        };
-        make_new_withdrawal.dispatch((request, access));
+        let request = request();
+        // Invoices filled in for an earlier amount are stale.
+        if let Ok(Some(msats)) = lnurl::invoice_msats(request.invoice.as_str()) {
+            if msats != request.amount * 1000 {
+                bail!(
+                    "The invoice is for {}, not {}. Create a new one",
+                    sats::format(msats / 1000, state.unit),
+                    sats::format(request.amount, state.unit)
+                )
+            }
+        }
+        match state.withdrawal_limits.check(
+            request.amount,
+            available,
+            withdrawn,
+            state.session_start,
+            state.unit,
+        ) {
+            Verdict::Block(reason) => bail!(reason),
+            Verdict::Confirm(reason) => {
+                confirmation.set(Some((reason, request)));
+                challenge.dispatch(());
+            }
+            Verdict::Allow => make_new_withdrawal.dispatch((request, None)),
+        }
         Ok(())
     };
+    let confirm = move || {
+        let challenge = challenge
+            .value()
+            .get()
+            .and_then(|challenge| challenge.ok())
+            .context("No challenge to sign yet")?;
+        let (_, request) = confirmation.get().context("Nothing to confirm")?;
+        make_new_withdrawal.dispatch((request, Some((challenge, signature.get()))));
+        confirmation.set(None);
+        Ok::<(), anyhow::Error>(())
+    };
+    let fill_invoice = create_action(|amount: &Sats| make_invoice_webln(*amount));
+    create_effect(move |_| {
+        if let Some(Ok(created)) = fill_invoice.value().get() {
+            invoice.set(created);
+        }
+    });
+    let book = create_rw_signal(address_book::load());
+    create_effect(move |_| address_book::save(&book.get()));
+    let resolve_destination = create_action(move |destination: &String| {
+        let destination = destination.clone();
+        let amount = amount.get_untracked();
+        async move {
+            let resolved = lnurl::resolve_invoice(destination.as_str(), amount).await?;
+            Ok::<_, String>((destination, resolved))
+        }
+    });
+    // The saved destination and the invoice requested from it, marked as
+    // used once a withdrawal to that invoice succeeded.
+    let resolved_from = create_rw_signal::<Option<(String, Invoice)>>(None);
+    create_effect(move |_| {
+        if let Some(Ok((destination, resolved))) = resolve_destination.value().get() {
+            invoice.set(resolved.clone());
+            resolved_from.set(Some((destination, resolved)));
+        }
+    });
+    create_effect(move |_| {
+        if let Some(Ok(_)) = make_new_withdrawal.value().get() {
+            if let Some((destination, resolved)) = resolved_from.get_untracked() {
+                if resolved == invoice.get_untracked() {
+                    book.update(|book| address_book::mark_used(book, destination.as_str()));
+                }
+            }
+        }
+    });
     let created_withdrawal = create_local_resource(
         move || make_new_withdrawal.version().get(),
         move |_| fetch_rw_signal(make_new_withdrawal.value()),
@@ -1165,10 +3351,31 @@ pub fn MakeWithdrawal(state: ReadSignal<MercadoState>) -> impl IntoView {
         <Stack spacing=Size::Em(1.0)>
             <h3>"Make Withdrawal"</h3>
             <div>
-                <NumberInput get=amount set=amount.write_only() step=1000.0 min=1.0 />
-                <label>"Amount (sats)"</label>
+                <label>"Amount"</label>
+                <SatsInput value=amount valid=amount_valid state=state min=1 available_of=Some(user) />
             </div>
             <TextInput get=invoice set=invoice.write_only() placeholder="Invoice" />
+            <Cond cond=webln::is_available() view=view!{
+                <div>
+                    <Button
+                        disabled=Signal::derive(move || !amount_valid.get() || fill_invoice.pending().get())
+                        on_click=move |_| {
+                            if amount_valid.get_untracked() {
+                                fill_invoice.dispatch(amount.get_untracked());
+                            }
+                        }
+                    >"Create invoice with WebLN"</Button>
+                    <small>{move || fill_invoice.value().get().and_then(|result| result.err())}</small>
+                </div>
+            } />
+            <AddressBook book=book on_select=move |destination| resolve_destination.dispatch(destination) />
+            <small>{move || {
+                if resolve_destination.pending().get() {
+                    Some("Requesting invoice...".to_string())
+                } else {
+                    resolve_destination.value().get().and_then(|result| result.err())
+                }
+            }}</small>
             <p>{
                 move || {
                     if let Some(message) = message.get() {
@@ -1176,7 +3383,7 @@ pub fn MakeWithdrawal(state: ReadSignal<MercadoState>) -> impl IntoView {
                     } else {
                         match created_withdrawal.get().flatten() {
                             Some(Ok(id)) => {
-                                view!{<Redirect path={format!("/wallet")} />}.into_view()
+                                view!{<Redirect path={format!("/wallet/{}", user)} />}.into_view()
                             }
                             Some(Err(e)) => {
                                 format!("{:?}", e).into_view()
@@ -1188,6 +3395,29 @@ pub fn MakeWithdrawal(state: ReadSignal<MercadoState>) -> impl IntoView {
                     }
                 }
             }</p>
+            {move || limits_context.get().and_then(|context| context.err()).map(|e| view!{
+                <p><small>"Couldn't check withdrawal limits: "{e}</small></p>
+            })}
+            {move || confirmation.get().map(|(reason, request)| view!{
+                <div>
+                    <p>{reason}". Withdrawing "<SatsDisplay amount=request.amount state=state />
+                        " to "<small style="word-break: break-all">{request.invoice}</small>
+                    </p>
+                    <p>"Sign this challenge with your key to confirm: "
+                        <kbd>{move || challenge.value().get().map(|challenge| challenge.unwrap_or_else(|e| e))}</kbd>
+                    </p>
+                    <TextInput get=signature set=set_signature placeholder="ECDSA Signature" />
+                    <ButtonGroup>
+                        <Button on_click=move |_| {
+                            match confirm() {
+                                Ok(_) => message.set(None),
+                                Err(e) => message.set(Some(e.to_string().into_view())),
+                            }
+                        }>"Confirm withdrawal"</Button>
+                        <Button on_click=move |_| confirmation.set(None)>"Cancel"</Button>
+                    </ButtonGroup>
+                </div>
+            })}
             <Button on_click=move |_| {
                 match add_bet() {
                     Ok(action) => message.set(None),
@@ -1198,3 +3428,113 @@ pub fn MakeWithdrawal(state: ReadSignal<MercadoState>) -> impl IntoView {
     }
     .into_view()
 }
+#[component]
+pub fn AddressBook<F>(book: RwSignal<Vec<SavedDestination>>, on_select: F) -> impl IntoView
+where
+    F: Fn(String) + Copy + 'static,
+{
+    let (label, set_label) = create_signal(String::new());
+    let (destination, set_destination) = create_signal(String::new());
+    let message = create_rw_signal::<Option<String>>(None);
+    let import = move |ev: web_sys::Event| {
+        let input = event_target::<web_sys::HtmlInputElement>(&ev);
+        spawn_local(async move {
+            let result = export::read_file(input).await.and_then(|json| {
+                book.try_update(|book| address_book::import(book, json.as_str()))
+                    .unwrap_or(Ok(0))
+            });
+            message.set(Some(match result {
+                Ok(count) => format!("Imported {} destination(s)", count),
+                Err(e) => e,
+            }));
+        });
+    };
+
+    view! {
+        <Collapsible>
+            <CollapsibleHeader slot>{move || format!("Saved destinations: {}", book.get().len())}</CollapsibleHeader>
+            <CollapsibleBody slot>
+                <TableContainer><Table bordered=true hoverable=true>
+                    <Thead><Tr>
+                        <Th>"Label"</Th>
+                        <Th>"Destination"</Th>
+                        <Th>"Last used"</Th>
+                        <Th>"Actions"</Th>
+                    </Tr></Thead>
+                    <Tbody><For each=move || address_book::sorted(book.get()) key=|saved| saved.destination.clone()
+                    children=move |saved: SavedDestination| {
+                        let destination = saved.destination.clone();
+                        let removed = saved.destination.clone();
+                        view!{
+                            <Tr>
+                                <Td>{saved.label}</Td>
+                                <Td><small>{saved.destination}</small></Td>
+                                <Td>{saved.last_used.map(|time| time.format("%Y-%m-%d %H:%M").to_string()).unwrap_or("Never".to_string())}</Td>
+                                <Td>
+                                    <Button on_click=move |_| on_select(destination.clone())>"Use"</Button>
+                                    <Button on_click=move |_| book.update(|book| book.retain(|saved| saved.destination != removed))>"Remove"</Button>
+                                </Td>
+                            </Tr>
+                        }
+                    }/></Tbody>
+                </Table></TableContainer>
+                <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(1.0)>
+                    <TextInput get=label set=set_label placeholder="Label" />
+                    <TextInput get=destination set=set_destination placeholder="Lightning Address or LNURL" />
+                    <Button on_click=move |_| {
+                        let result = book.try_update(|book| address_book::add(book, label.get(), destination.get()));
+                        match result {
+                            Some(Err(e)) => message.set(Some(e)),
+                            _ => {
+                                set_label.set(String::new());
+                                set_destination.set(String::new());
+                                message.set(None);
+                            }
+                        }
+                    }>"Save"</Button>
+                </Stack>
+                <Stack orientation=StackOrientation::Horizontal spacing=Size::Em(1.0)>
+                    <Button on_click=move |_| {
+                        let result = address_book::to_json(&book.get()).and_then(|json| {
+                            export::download("mercado-address-book.json", "application/json", json.as_str())
+                        });
+                        message.set(result.err());
+                    }>"Export"</Button>
+                    <label>"Import "<input type="file" accept="application/json" on:change=import /></label>
+                </Stack>
+                <small>{move || message.get()}</small>
+            </CollapsibleBody>
+        </Collapsible>
+    }
+}
+#[component]
+pub fn WeblnPay(
+    invoice: Invoice,
+    tx: Option<RowId>,
+    state: ReadSignal<MercadoState>,
+) -> impl IntoView {
+    if !webln::is_available() {
+        return view! {}.into_view();
+    }
+    let access = if let Some(access) = state.get_untracked().access {
+        access
+    } else {
+        return view! {}.into_view();
+    };
+    let pay = create_action(move |_: &()| pay_with_webln(invoice.clone(), tx, access.clone()));
+    view! {
+        <span>
+            <Button disabled=pay.pending() on_click=move |_| pay.dispatch(())>"Pay with WebLN"</Button>
+            <small>{move || if pay.pending().get() {
+                "Waiting for payment...".to_string()
+            } else {
+                match pay.value().get() {
+                    Some(Ok(message)) => message,
+                    Some(Err(e)) => format!("{:?}", e),
+                    None => "".to_string(),
+                }
+            }}</small>
+        </span>
+    }
+    .into_view()
+}
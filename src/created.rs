@@ -0,0 +1,21 @@
+//! Predictions created in this browser. The API doesn't record who created a
+//! prediction, so this is how `PredictionOverview` knows to show the creator
+//! panel.
+use crate::storage;
+use mercado::api::RowId;
+
+const STORAGE_KEY: &str = "mercado-created-predictions";
+
+pub fn load() -> Vec<RowId> {
+    storage::load(STORAGE_KEY).unwrap_or_default()
+}
+pub fn remember(prediction: RowId) {
+    let mut created = load();
+    if !created.contains(&prediction) {
+        created.push(prediction);
+        storage::save(STORAGE_KEY, &created)
+    }
+}
+pub fn is_mine(prediction: RowId) -> bool {
+    load().contains(&prediction)
+}
@@ -0,0 +1,79 @@
+//! Unfinished predictions, saved in the browser's localStorage while they're
+//! being edited.
+use crate::storage;
+use chrono::{DateTime, Duration, Utc};
+use mercado::api::{NewPredictionRequest, PredictionOverviewResponse, UserPubKey};
+use serde::{Deserialize, Serialize};
+
+const STORAGE_KEY: &str = "mercado-prediction-drafts";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Draft {
+    pub id: i64,
+    pub saved: DateTime<Utc>,
+    pub prediction: String,
+    pub trading_end: DateTime<Utc>,
+    pub decision_period_sec: u32,
+    pub judge_share_ppm: u32,
+    pub judge_count: u32,
+    pub judges: Vec<UserPubKey>,
+}
+impl Draft {
+    pub fn new(id: i64, request: &NewPredictionRequest) -> Self {
+        Self {
+            id,
+            saved: Utc::now(),
+            prediction: request.prediction.clone(),
+            trading_end: request.trading_end,
+            decision_period_sec: request.decision_period_sec,
+            judge_share_ppm: request.judge_share_ppm,
+            judge_count: request.judge_count,
+            judges: request.judges.clone(),
+        }
+    }
+    /// A copy of an existing prediction. Its trading end is moved a week
+    /// ahead if it already passed.
+    pub fn from_overview(prediction: &PredictionOverviewResponse, judges: Vec<UserPubKey>) -> Self {
+        let trading_end = if prediction.trading_end > Utc::now() {
+            prediction.trading_end
+        } else {
+            Utc::now() + Duration::weeks(1)
+        };
+        Self {
+            id: new_id(),
+            saved: Utc::now(),
+            prediction: prediction.name.clone(),
+            trading_end,
+            decision_period_sec: prediction.decision_period_sec,
+            judge_share_ppm: prediction.judge_share_ppm,
+            judge_count: prediction.judge_count,
+            judges,
+        }
+    }
+    /// Nothing worth keeping was entered yet.
+    pub fn is_empty(&self) -> bool {
+        self.prediction.trim().is_empty() && self.judges.is_empty()
+    }
+}
+
+pub fn new_id() -> i64 {
+    Utc::now().timestamp_millis()
+}
+pub fn load() -> Vec<Draft> {
+    storage::load(STORAGE_KEY).unwrap_or_default()
+}
+pub fn get(id: i64) -> Option<Draft> {
+    load().into_iter().find(|draft| draft.id == id)
+}
+/// Saves the draft, replacing an older version of it.
+pub fn put(draft: Draft) {
+    let mut drafts = load();
+    drafts.retain(|other| other.id != draft.id);
+    drafts.push(draft);
+    storage::save(STORAGE_KEY, &drafts)
+}
+pub fn remove(id: i64) {
+    let mut drafts = load();
+    drafts.retain(|draft| draft.id != id);
+    storage::save(STORAGE_KEY, &drafts)
+}
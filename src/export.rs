@@ -0,0 +1,403 @@
+//! CSV and JSON export of wallet and betting history.
+//!
+//! Both formats share one stable schema. CSV columns and JSON keys are
+//! identical and appear in this order:
+//!
+//! | Export       | Columns                                                                   |
+//! |--------------|---------------------------------------------------------------------------|
+//! | Transactions | `id, date, direction, amount_sats, state, payment_request, payment_hash` |
+//! | Bets         | `id, date, user, prediction, prediction_name, bet, amount_sats`           |
+//! | Judges       | `date, user, prediction, prediction_name, state`                          |
+//!
+//! `date` is RFC 3339 in UTC. Bets and judges are dated by the trading end of
+//! their prediction. `user` is the bettor or judge. The date range filter is
+//! inclusive on both ends.
+use crate::fetchers::*;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::future::try_join_all;
+use leptos::{document, set_timeout};
+use mercado::api::*;
+use serde::Serialize;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, HtmlInputElement, Url};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    Transactions,
+    Bets,
+    Judges,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+pub trait Record: Serialize {
+    const COLUMNS: &'static [&'static str];
+    fn date(&self) -> DateTime<Utc>;
+    fn values(&self) -> Vec<String>;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TxRecord {
+    pub id: RowId,
+    pub date: DateTime<Utc>,
+    pub direction: String,
+    pub amount_sats: Sats,
+    pub state: String,
+    pub payment_request: String,
+    pub payment_hash: String,
+}
+impl Record for TxRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "date",
+        "direction",
+        "amount_sats",
+        "state",
+        "payment_request",
+        "payment_hash",
+    ];
+    fn date(&self) -> DateTime<Utc> {
+        self.date
+    }
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.date.to_rfc3339(),
+            self.direction.clone(),
+            self.amount_sats.to_string(),
+            self.state.clone(),
+            self.payment_request.clone(),
+            self.payment_hash.clone(),
+        ]
+    }
+}
+impl From<LedgerEntry> for TxRecord {
+    fn from(entry: LedgerEntry) -> Self {
+        let state = Settlement::of(&entry.tx).to_string();
+        match entry.tx.tx_type {
+            TxType::Bolt11 { details, .. } => Self {
+                id: entry.id,
+                date: entry.tx.created,
+                direction: format!("{:?}", entry.direction),
+                amount_sats: entry.tx.amount,
+                state,
+                payment_request: details.payment_request,
+                payment_hash: details.payment_hash,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BetRecord {
+    pub id: RowId,
+    pub date: DateTime<Utc>,
+    pub user: UserPubKey,
+    pub prediction: RowId,
+    pub prediction_name: String,
+    pub bet: bool,
+    pub amount_sats: Sats,
+}
+impl Record for BetRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "date",
+        "user",
+        "prediction",
+        "prediction_name",
+        "bet",
+        "amount_sats",
+    ];
+    fn date(&self) -> DateTime<Utc> {
+        self.date
+    }
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.date.to_rfc3339(),
+            self.user.to_string(),
+            self.prediction.to_string(),
+            self.prediction_name.clone(),
+            self.bet.to_string(),
+            self.amount_sats.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JudgeRecord {
+    pub date: DateTime<Utc>,
+    pub user: UserPubKey,
+    pub prediction: RowId,
+    pub prediction_name: String,
+    pub state: String,
+}
+impl Record for JudgeRecord {
+    const COLUMNS: &'static [&'static str] =
+        &["date", "user", "prediction", "prediction_name", "state"];
+    fn date(&self) -> DateTime<Utc> {
+        self.date
+    }
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.date.to_rfc3339(),
+            self.user.to_string(),
+            self.prediction.to_string(),
+            self.prediction_name.clone(),
+            self.state.clone(),
+        ]
+    }
+}
+
+/// Quotes values as needed and prefixes ones a spreadsheet would evaluate as
+/// a formula with `'`.
+fn escape_csv(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+pub fn to_csv<R: Record>(records: &[R]) -> String {
+    let mut csv = R::COLUMNS.join(",") + "\r\n";
+    for record in records {
+        let values = record
+            .values()
+            .iter()
+            .map(|value| escape_csv(value))
+            .collect::<Vec<_>>();
+        csv += &(values.join(",") + "\r\n");
+    }
+    csv
+}
+pub fn to_json<R: Record>(records: &[R]) -> Result<String, String> {
+    serde_json::to_string_pretty(records).map_err(|e| e.to_string())
+}
+pub fn in_range<R: Record>(
+    mut records: Vec<R>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Vec<R> {
+    records.retain(|record| {
+        let date = record.date().date_naive();
+        from.map_or(true, |from| date >= from) && to.map_or(true, |to| date <= to)
+    });
+    records
+}
+fn encode<R: Record>(
+    records: Vec<R>,
+    format: ExportFormat,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<String, String> {
+    let records = in_range(records, from, to);
+    match format {
+        ExportFormat::Csv => Ok(to_csv(&records)),
+        ExportFormat::Json => to_json(&records),
+    }
+}
+
+pub async fn tx_records(user: UserPubKey, access: AccessRequest) -> Result<Vec<TxRecord>, String> {
+    Ok(get_ledger(user, access)
+        .await?
+        .into_iter()
+        .map(TxRecord::from)
+        .collect())
+}
+pub async fn bet_records(
+    user: Option<UserPubKey>,
+    access: AccessRequest,
+) -> Result<Vec<BetRecord>, String> {
+    let request = PredictionUserRequest {
+        prediction: None,
+        user,
+    };
+    let bets = get_bets(request, access).await?;
+    let predictions = get_prediction_overviews(bets.iter().map(|bet| bet.prediction)).await?;
+    Ok(bets
+        .into_iter()
+        .map(|bet| {
+            let prediction = &predictions[&bet.prediction];
+            BetRecord {
+                id: bet.id,
+                date: prediction.trading_end,
+                user: bet.user,
+                prediction: bet.prediction,
+                prediction_name: prediction.name.clone(),
+                bet: bet.bet,
+                amount_sats: bet.amount,
+            }
+        })
+        .collect())
+}
+pub async fn judge_records(
+    user: Option<UserPubKey>,
+    access: AccessRequest,
+) -> Result<Vec<JudgeRecord>, String> {
+    let judges = get_judges(None, user).await?;
+    let predictions = get_prediction_overviews(judges.iter().map(|judge| judge.prediction)).await?;
+    let states = try_join_all(judges.iter().map(|judge| {
+        let request = JudgeRequest {
+            prediction: judge.prediction,
+            user: judge.user,
+        };
+        let access = access.clone();
+        async move {
+            client()
+                .get_judge(request, access)
+                .await
+                .map_err(map_any_err)
+                .map(|judge| judge.state.to_string())
+        }
+    }))
+    .await?;
+    Ok(judges
+        .into_iter()
+        .zip(states)
+        .map(|(judge, state)| {
+            let prediction = &predictions[&judge.prediction];
+            JudgeRecord {
+                date: prediction.trading_end,
+                user: judge.user,
+                prediction: judge.prediction,
+                prediction_name: prediction.name.clone(),
+                state,
+            }
+        })
+        .collect())
+}
+
+/// Fetches, filters and downloads the requested history. `user` defaults to
+/// the logged in user for transactions and to everyone for bets and judges.
+pub async fn export_history(
+    kind: ExportKind,
+    format: ExportFormat,
+    user: Option<UserPubKey>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    access: AccessRequest,
+) -> Result<(), String> {
+    let name = match kind {
+        ExportKind::Transactions => "transactions",
+        ExportKind::Bets => "bets",
+        ExportKind::Judges => "judges",
+    };
+    let content = match kind {
+        ExportKind::Transactions => encode(
+            tx_records(user.unwrap_or(access.user), access).await?,
+            format,
+            from,
+            to,
+        ),
+        ExportKind::Bets => encode(bet_records(user, access).await?, format, from, to),
+        ExportKind::Judges => encode(judge_records(user, access).await?, format, from, to),
+    }?;
+    let (extension, mime) = match format {
+        ExportFormat::Csv => ("csv", "text/csv"),
+        ExportFormat::Json => ("json", "application/json"),
+    };
+    download(
+        format!(
+            "mercado-{}-{}.{}",
+            name,
+            Utc::now().format("%Y-%m-%d"),
+            extension
+        )
+        .as_str(),
+        mime,
+        content.as_str(),
+    )
+}
+
+fn map_js_err(error: JsValue) -> String {
+    format!("{:?}", error)
+}
+pub fn download(filename: &str, mime: &str, content: &str) -> Result<(), String> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(content));
+    let mut options = BlobPropertyBag::new();
+    options.type_(mime);
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &options).map_err(map_js_err)?;
+    let url = Url::create_object_url_with_blob(&blob).map_err(map_js_err)?;
+    let anchor = document()
+        .create_element("a")
+        .map_err(map_js_err)?
+        .dyn_into::<HtmlAnchorElement>()
+        .map_err(|_| "couldn't create download link".to_string())?;
+    anchor.set_href(url.as_str());
+    anchor.set_download(filename);
+    anchor.click();
+    // Revoking right away can cancel the download before the browser starts it.
+    set_timeout(
+        move || {
+            let _ = Url::revoke_object_url(url.as_str());
+        },
+        std::time::Duration::from_secs(1),
+    );
+    Ok(())
+}
+/// Text of the first file selected in a file input.
+pub async fn read_file(input: HtmlInputElement) -> Result<String, String> {
+    let file = input
+        .files()
+        .and_then(|files| files.get(0))
+        .ok_or("No file selected".to_string())?;
+    JsFuture::from(file.text())
+        .await
+        .map_err(map_js_err)?
+        .as_string()
+        .ok_or("The file isn't text".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_values_are_unchanged() {
+        assert_eq!(escape_csv("Bitcoin above 100k"), "Bitcoin above 100k");
+        assert_eq!(escape_csv(""), "");
+    }
+    #[test]
+    fn quotes_separators_and_quotes() {
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"yes\""), "\"say \"\"yes\"\"\"");
+        assert_eq!(escape_csv("two\nlines"), "\"two\nlines\"");
+    }
+    #[test]
+    fn guards_formulas() {
+        assert_eq!(escape_csv("=HYPERLINK(1)"), "'=HYPERLINK(1)");
+        assert_eq!(escape_csv("+1"), "'+1");
+        assert_eq!(escape_csv("-1"), "'-1");
+        assert_eq!(escape_csv("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(escape_csv("\tx"), "'\tx");
+        assert_eq!(escape_csv("=1,2"), "\"'=1,2\"");
+        assert_eq!(escape_csv("\rx"), "\"'\rx\"");
+    }
+    #[test]
+    fn csv_has_header_and_crlf_rows() {
+        let user = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        let records = vec![JudgeRecord {
+            date: DateTime::<Utc>::from_timestamp(0, 0).unwrap(),
+            user: user.parse().unwrap(),
+            prediction: 1,
+            prediction_name: "=cmd".to_string(),
+            state: "Accepted".to_string(),
+        }];
+        assert_eq!(
+            to_csv(&records),
+            format!(
+                "date,user,prediction,prediction_name,state\r\n1970-01-01T00:00:00+00:00,{},1,'=cmd,Accepted\r\n",
+                user
+            )
+        );
+    }
+}
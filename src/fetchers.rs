@@ -1,10 +1,306 @@
-use std::str::FromStr;
+use std::{collections::HashMap, future::Future, str::FromStr};
 
-use crate::{MercadoState, URL};
-use leptos::{ReadSignal, Resource, RwSignal, SignalGetUntracked, SignalSet, WriteSignal};
+use crate::{basket::BasketItem, webln, MercadoState, URL};
+use chrono::{DateTime, Duration, Utc};
+use futures::future::{join_all, try_join_all};
+use js_sys::Promise;
+use leptos::{
+    window, ReadSignal, Resource, RwSignal, SignalGetUntracked, SignalSet, SignalUpdate,
+    WriteSignal,
+};
 use mercado::api::*;
 use mercado::client::Client;
 use mercado::secp256k1::ecdsa::Signature;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::Response;
+
+const SETTLEMENT_POLLS: u32 = 30;
+/// Ten minutes at `SETTLEMENT_POLL_INTERVAL_MS`.
+const DEPOSIT_POLLS: u32 = 300;
+const SETTLEMENT_POLL_INTERVAL_MS: i32 = 2000;
+
+#[derive(Debug, Clone)]
+pub struct LedgerEntry {
+    pub id: RowId,
+    pub direction: TxDirection,
+    pub tx: Tx,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Settlement {
+    Pending,
+    Settled,
+    Failed,
+}
+impl Settlement {
+    pub fn of(tx: &Tx) -> Self {
+        match &tx.tx_type {
+            TxType::Bolt11 { state, .. } => match state {
+                TxState::Settled => Self::Settled,
+                TxState::Failed => Self::Failed,
+                _ => Self::Pending,
+            },
+        }
+    }
+}
+impl std::fmt::Display for Settlement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pending => write!(f, "Pending"),
+            Self::Settled => write!(f, "Settled"),
+            Self::Failed => write!(f, "Failed"),
+        }
+    }
+}
+
+/// Attribution of the locked part of a balance (total minus available).
+#[derive(Debug, Clone)]
+pub struct BalanceParts {
+    pub available: Sats,
+    pub total: Sats,
+    pub bets: Vec<(Bet, PredictionOverviewResponse)>,
+    pub withdrawals: Vec<LedgerEntry>,
+}
+impl BalanceParts {
+    pub fn locked(&self) -> i64 {
+        self.total as i64 - self.available as i64
+    }
+    pub fn explained(&self) -> i64 {
+        let bets = self.bets.iter().map(|(bet, _)| bet.amount as i64);
+        let withdrawals = self.withdrawals.iter().map(|entry| entry.tx.amount as i64);
+        bets.chain(withdrawals).sum()
+    }
+    /// Locked funds that aren't covered by an open bet or pending withdrawal.
+    pub fn unexplained(&self) -> i64 {
+        self.locked() - self.explained()
+    }
+}
+
+/// What could be loaded, and the predictions that couldn't with the error.
+#[derive(Debug, Clone)]
+pub struct Partial<T> {
+    pub items: Vec<T>,
+    pub failed: Vec<(RowId, String)>,
+}
+impl<T> FromIterator<Result<T, (RowId, String)>> for Partial<T> {
+    fn from_iter<I: IntoIterator<Item = Result<T, (RowId, String)>>>(results: I) -> Self {
+        let mut partial = Self {
+            items: vec![],
+            failed: vec![],
+        };
+        for result in results {
+            match result {
+                Ok(item) => partial.items.push(item),
+                Err(failed) => partial.failed.push(failed),
+            }
+        }
+        partial
+    }
+}
+#[derive(Debug, Clone)]
+pub struct JudgeTask {
+    pub judge: Judge,
+    pub prediction: PredictionOverviewResponse,
+}
+impl JudgeTask {
+    /// When the judges' decision period ends.
+    pub fn deadline(&self) -> DateTime<Utc> {
+        self.prediction.trading_end + Duration::seconds(self.prediction.decision_period_sec as i64)
+    }
+}
+
+/// Judges of a prediction by state. Judge states are only visible to admins
+/// and the judges themselves, everything else is counted as unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JudgeTally {
+    pub nominated: usize,
+    pub pending: usize,
+    pub accepted: usize,
+    pub refused: usize,
+    pub decided_true: usize,
+    pub decided_false: usize,
+    pub unknown: usize,
+}
+impl JudgeTally {
+    pub fn new(states: &[(UserPubKey, Option<JudgeState>)]) -> Self {
+        let mut tally = Self {
+            nominated: states.len(),
+            ..Default::default()
+        };
+        for (_, state) in states {
+            match state {
+                Some(JudgeState::Nominated) => tally.pending += 1,
+                Some(JudgeState::Accepted) => tally.accepted += 1,
+                Some(JudgeState::Refused) => tally.refused += 1,
+                Some(JudgeState::Resolved(decision)) => {
+                    tally.accepted += 1;
+                    if *decision {
+                        tally.decided_true += 1;
+                    } else {
+                        tally.decided_false += 1;
+                    }
+                }
+                None => tally.unknown += 1,
+            }
+        }
+        tally
+    }
+    pub fn decided(&self) -> usize {
+        self.decided_true + self.decided_false
+    }
+    /// Acceptances still missing to reach `judge_count`.
+    pub fn missing(&self, judge_count: u32) -> usize {
+        (judge_count as usize).saturating_sub(self.accepted)
+    }
+    /// Whether the judges that haven't answered yet can't make up the missing acceptances.
+    pub fn cannot_resolve(&self, judge_count: u32) -> bool {
+        self.missing(judge_count) > self.pending + self.unknown
+    }
+}
+
+/// One prediction a judge was nominated for. `state` is `None` if the viewer
+/// isn't allowed to see the judge's state.
+#[derive(Debug, Clone)]
+pub struct JudgeEarning {
+    pub prediction: PredictionOverviewResponse,
+    pub state: Option<JudgeState>,
+}
+impl JudgeEarning {
+    /// Share of one judge: the judges' part of the pot split by `judge_count`.
+    pub fn share(&self) -> Sats {
+        let pot = (self.prediction.ratio.0 + self.prediction.ratio.1) as u128;
+        let judges = (self.prediction.judge_count as u128).max(1);
+        (pot * self.prediction.judge_share_ppm as u128 / 1_000_000 / judges) as Sats
+    }
+    pub fn decision(&self) -> Option<bool> {
+        match self.state {
+            Some(JudgeState::Resolved(decision)) => Some(decision),
+            _ => None,
+        }
+    }
+    pub fn outcome(&self) -> Option<bool> {
+        match self.prediction.state {
+            MarketState::Resolved(outcome) => Some(outcome),
+            _ => None,
+        }
+    }
+    /// Paid out once the judge decided and the market resolved.
+    pub fn earned(&self) -> Option<Sats> {
+        self.decision().and(self.outcome()).map(|_| self.share())
+    }
+    /// Expected if the judge accepted and the market is still running.
+    pub fn expected(&self) -> Option<Sats> {
+        match self.state {
+            Some(JudgeState::Accepted) if is_open(&self.prediction.state) => Some(self.share()),
+            _ => None,
+        }
+    }
+    pub fn agrees(&self) -> Option<bool> {
+        Some(self.decision()? == self.outcome()?)
+    }
+    pub fn resolved_at(&self) -> DateTime<Utc> {
+        self.prediction.trading_end + Duration::seconds(self.prediction.decision_period_sec as i64)
+    }
+}
+
+pub fn is_open(state: &MarketState) -> bool {
+    matches!(
+        state,
+        MarketState::WaitingForJudges
+            | MarketState::Trading
+            | MarketState::TradingStop
+            | MarketState::WaitingForDecision
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalancePoint {
+    pub time: DateTime<Utc>,
+    pub available: i64,
+    pub total: i64,
+}
+
+/// Payout of a winning bet of `amount` if the market closes at `ratio`
+/// (true sats, false sats, including the bet). The judges' share is taken
+/// from the whole pot before it's split among the winners.
+pub fn payout(amount: Sats, bet: bool, ratio: (Sats, Sats), judge_share_ppm: u32) -> Sats {
+    let own = (if bet { ratio.0 } else { ratio.1 }) as u128;
+    if own == 0 {
+        return amount;
+    }
+    let pot = (ratio.0 + ratio.1) as u128 * (1_000_000 - judge_share_ppm.min(1_000_000) as u128)
+        / 1_000_000;
+    (amount as u128 * pot / own) as Sats
+}
+
+/// A user's bets on one prediction.
+#[derive(Debug, Clone, Default)]
+pub struct Position {
+    pub bets: Vec<Bet>,
+    pub on_true: Sats,
+    pub on_false: Sats,
+}
+impl Position {
+    pub fn new(bets: Vec<Bet>) -> Self {
+        let stake = |side: bool| {
+            bets.iter()
+                .filter(|bet| bet.bet == side)
+                .map(|bet| bet.amount)
+                .sum()
+        };
+        Self {
+            on_true: stake(true),
+            on_false: stake(false),
+            bets,
+        }
+    }
+    pub fn stake(&self) -> Sats {
+        self.on_true + self.on_false
+    }
+    /// Paid out if `outcome` wins at `ratio`, which already includes these bets.
+    pub fn payout(&self, outcome: bool, ratio: (Sats, Sats), judge_share_ppm: u32) -> Sats {
+        let stake = if outcome { self.on_true } else { self.on_false };
+        payout(stake, outcome, ratio, judge_share_ppm)
+    }
+    /// Payout minus everything staked.
+    pub fn net(&self, outcome: bool, ratio: (Sats, Sats), judge_share_ppm: u32) -> i128 {
+        self.payout(outcome, ratio, judge_share_ppm) as i128 - self.stake() as i128
+    }
+}
+
+/// A user's position in one prediction, for the portfolio.
+#[derive(Debug, Clone)]
+pub struct Holding {
+    pub prediction: PredictionOverviewResponse,
+    pub position: Position,
+}
+impl Holding {
+    pub fn is_open(&self) -> bool {
+        is_open(&self.prediction.state)
+    }
+    fn net(&self, outcome: bool) -> i128 {
+        self.position.net(
+            outcome,
+            self.prediction.ratio,
+            self.prediction.judge_share_ppm,
+        )
+    }
+    /// Closed without an outcome, so the stake was refunded.
+    pub fn is_refunded(&self) -> bool {
+        !self.is_open() && !matches!(self.prediction.state, MarketState::Resolved(_))
+    }
+    /// Net result once resolved.
+    pub fn realised(&self) -> Option<i128> {
+        match self.prediction.state {
+            MarketState::Resolved(outcome) => Some(self.net(outcome)),
+            _ => None,
+        }
+    }
+    /// Net result if true or false wins at current ratios, while open.
+    pub fn projected(&self) -> Option<(i128, i128)> {
+        self.is_open().then(|| (self.net(true), self.net(false)))
+    }
+}
 
 pub fn client() -> Client {
     Client::new(URL.to_string())
@@ -25,6 +321,21 @@ pub async fn get_prediction_overview(
         .await
         .map_err(map_any_err)
 }
+/// Overviews of the given predictions, each fetched once.
+pub async fn get_prediction_overviews(
+    predictions: impl IntoIterator<Item = RowId>,
+) -> Result<HashMap<RowId, PredictionOverviewResponse>, String> {
+    let mut ids = predictions.into_iter().collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+    let overviews = try_join_all(ids.iter().map(|id| async move {
+        get_prediction_overview(*id)
+            .await
+            .map_err(|e| format!("Couldn't load prediction {}: {}", id, e))
+    }))
+    .await?;
+    Ok(ids.into_iter().zip(overviews).collect())
+}
 pub async fn get_judges(
     prediction: Option<RowId>,
     user: Option<UserPubKey>,
@@ -47,6 +358,98 @@ pub async fn get_judge(
         Err("Not logged in".to_string())
     }
 }
+/// Everything the user was nominated to judge, with the state of the market.
+/// Nominations that couldn't be loaded are listed in `failed` by prediction.
+pub async fn get_judge_tasks(
+    user: UserPubKey,
+    access: AccessRequest,
+) -> Result<Partial<JudgeTask>, String> {
+    let judges = get_judges(None, Some(user)).await?;
+    let tasks = join_all(judges.into_iter().map(|judge| {
+        let request = JudgeRequest {
+            prediction: judge.prediction,
+            user: judge.user,
+        };
+        let access = access.clone();
+        async move {
+            let task = async {
+                let judge = client()
+                    .get_judge(request, access)
+                    .await
+                    .map_err(map_any_err)?;
+                let prediction = get_prediction_overview(judge.prediction).await?;
+                Ok::<_, String>(JudgeTask { judge, prediction })
+            };
+            task.await.map_err(|e| (judge.prediction, e))
+        }
+    }))
+    .await;
+    Ok(tasks.into_iter().collect())
+}
+pub async fn get_judge_states(
+    prediction: RowId,
+    access: Option<AccessRequest>,
+) -> Result<Vec<(UserPubKey, Option<JudgeState>)>, String> {
+    let mut states = vec![];
+    for judge in get_judges(Some(prediction), None).await? {
+        let state = if let Some(access) = access.clone() {
+            let request = JudgeRequest {
+                prediction,
+                user: judge.user,
+            };
+            client()
+                .get_judge(request, access)
+                .await
+                .ok()
+                .map(|judge| judge.state)
+        } else {
+            None
+        };
+        states.push((judge.user, state));
+    }
+    Ok(states)
+}
+/// A prediction to propose again, with its judges minus those who refused.
+/// Judges whose state isn't visible to the user are kept, so the creator can
+/// replace them by hand.
+pub async fn get_reproposal(
+    prediction: RowId,
+    access: Option<AccessRequest>,
+) -> Result<(PredictionOverviewResponse, Vec<UserPubKey>), String> {
+    let overview = get_prediction_overview(prediction).await?;
+    let mut judges = vec![];
+    for (judge, state) in get_judge_states(prediction, access).await? {
+        if state != Some(JudgeState::Refused) {
+            judges.push(judge);
+        }
+    }
+    Ok((overview, judges))
+}
+pub async fn get_judge_earnings(
+    user: UserPubKey,
+    access: Option<AccessRequest>,
+) -> Result<Vec<JudgeEarning>, String> {
+    let mut earnings = vec![];
+    for judge in get_judges(None, Some(user)).await? {
+        let state = if let Some(access) = access.clone() {
+            let request = JudgeRequest {
+                prediction: judge.prediction,
+                user,
+            };
+            client()
+                .get_judge(request, access)
+                .await
+                .ok()
+                .map(|judge| judge.state)
+        } else {
+            None
+        };
+        let prediction = get_prediction_overview(judge.prediction).await?;
+        earnings.push(JudgeEarning { prediction, state });
+    }
+    earnings.sort_by_key(|earning| std::cmp::Reverse(earning.resolved_at()));
+    Ok(earnings)
+}
 pub async fn get_bets(
     request: PredictionUserRequest,
     access: AccessRequest,
@@ -56,6 +459,45 @@ pub async fn get_bets(
         .await
         .map_err(map_any_err)
 }
+/// The user's position in a prediction.
+pub async fn get_position(prediction: RowId, access: AccessRequest) -> Result<Position, String> {
+    let request = PredictionUserRequest {
+        prediction: Some(prediction),
+        user: Some(access.user),
+    };
+    Ok(Position::new(get_bets(request, access).await?))
+}
+/// The user's bets grouped by prediction. Predictions that couldn't be loaded
+/// are listed in `failed`.
+pub async fn get_portfolio(access: AccessRequest) -> Result<Partial<Holding>, String> {
+    let request = PredictionUserRequest {
+        prediction: None,
+        user: Some(access.user),
+    };
+    let mut bets = get_bets(request, access).await?;
+    bets.sort_by_key(|bet| bet.prediction);
+    let mut groups = vec![];
+    let mut bets = bets.into_iter().peekable();
+    while let Some(first) = bets.next() {
+        let id = first.prediction;
+        let mut group = vec![first];
+        while let Some(bet) = bets.next_if(|bet| bet.prediction == id) {
+            group.push(bet);
+        }
+        groups.push((id, group));
+    }
+    let holdings = join_all(groups.into_iter().map(|(id, group)| async move {
+        match get_prediction_overview(id).await {
+            Ok(prediction) => Ok(Holding {
+                prediction,
+                position: Position::new(group),
+            }),
+            Err(e) => Err((id, e)),
+        }
+    }))
+    .await;
+    Ok(holdings.into_iter().collect())
+}
 pub async fn get_prediction_ratio(request: PredictionRequest) -> Result<(Sats, Sats), String> {
     client()
         .get_prediction_ratio(request)
@@ -81,6 +523,40 @@ pub async fn refuse_nomination(
         .await
         .map_err(map_any_err)
 }
+/// Runs `step` for the items one after another. `progress` is called with
+/// the number of handled items after each one.
+async fn one_by_one<T: Clone, R, Fut: Future<Output = R>>(
+    items: Vec<T>,
+    step: impl Fn(T) -> Fut,
+    progress: impl Fn(usize),
+) -> Vec<(T, R)> {
+    let mut results = vec![];
+    for (i, item) in items.into_iter().enumerate() {
+        let result = step(item.clone()).await;
+        results.push((item, result));
+        progress(i + 1);
+    }
+    results
+}
+/// Accepts or refuses the nominations one after another, see `one_by_one`.
+pub async fn respond_to_nominations(
+    nominations: Vec<NominationRequest>,
+    accept: bool,
+    access: AccessRequest,
+    progress: impl Fn(usize),
+) -> Vec<(NominationRequest, Result<(), String>)> {
+    let respond = move |nomination| {
+        let access = access.clone();
+        async move {
+            if accept {
+                accept_nomination(nomination, access).await
+            } else {
+                refuse_nomination(nomination, access).await
+            }
+        }
+    };
+    one_by_one(nominations, respond, progress).await
+}
 pub async fn create_login_challenge(user: String) -> Result<String, String> {
     let user = UserPubKey::from_str(user.as_str())
         .map_err(|e| e.into())
@@ -114,12 +590,38 @@ pub async fn try_login(
         challenge: request.challenge,
     };
     let user_detail = get_user(user, access.clone()).await?;
-    set_state.set(MercadoState {
-        access: Some(access.clone()),
-        user: Some(user_detail),
+    set_state.update(|state| {
+        state.access = Some(access.clone());
+        state.user = Some(user_detail);
+        state.session_start = Some(Utc::now());
     });
     Ok(format!("Successfull login as {}", user))
 }
+/// Checks a signature over a fresh login challenge without replacing the
+/// current session and returns the new access for a single request.
+pub async fn reauthenticate(
+    user: UserPubKey,
+    signature: String,
+    challenge: String,
+) -> Result<AccessRequest, String> {
+    let sig = Signature::from_str(signature.trim())
+        .map_err(|e| e.into())
+        .map_err(map_any_err)?;
+    let request = LoginRequest {
+        user,
+        sig,
+        challenge,
+    };
+    client()
+        .try_login(request.clone())
+        .await
+        .map_err(map_any_err)?;
+    Ok(AccessRequest {
+        user: request.user,
+        sig: request.sig,
+        challenge: request.challenge,
+    })
+}
 pub async fn check_login(access: Option<AccessRequest>) -> Result<String, String> {
     if let Some(access) = access {
         client().check_login(access).await.map_err(map_any_err)?;
@@ -132,6 +634,89 @@ pub async fn get_username(user: UserPubKey) -> Result<String, String> {
     let name = client().get_username(user).await.map_err(map_any_err)?;
     Ok(name)
 }
+/// Everyone who has bet or judged, with their username if they set one.
+pub async fn get_known_users(access: AccessRequest) -> Result<Vec<(UserPubKey, String)>, String> {
+    let request = PredictionUserRequest {
+        prediction: None,
+        user: None,
+    };
+    let mut users = get_bets(request, access)
+        .await?
+        .into_iter()
+        .map(|bet| bet.user)
+        .chain(
+            get_judges(None, None)
+                .await?
+                .into_iter()
+                .map(|judge| judge.user),
+        )
+        .collect::<Vec<_>>();
+    users.sort();
+    users.dedup();
+    let names = join_all(users.iter().map(|user| get_username(*user))).await;
+    Ok(users
+        .into_iter()
+        .zip(names)
+        .map(|(user, name)| (user, name.unwrap_or_default()))
+        .collect())
+}
+#[derive(Debug, Clone)]
+pub struct JudgeCandidate {
+    pub user: UserPubKey,
+    pub name: String,
+    /// Predictions the user was nominated to judge.
+    pub nominations: usize,
+    /// Of those, how many resolved.
+    pub resolved: usize,
+}
+/// Users to nominate as judges, most experienced first. Users who only bet
+/// are included for admins, who are the only ones allowed to list all bets.
+pub async fn get_judge_candidates(
+    admin: Option<AccessRequest>,
+) -> Result<Vec<JudgeCandidate>, String> {
+    let resolved = get_predictions(())
+        .await?
+        .into_iter()
+        .filter(|prediction| matches!(prediction.state, MarketState::Resolved(_)))
+        .map(|prediction| prediction.id)
+        .collect::<Vec<_>>();
+    let judges = get_judges(None, None).await?;
+    let mut users = judges.iter().map(|judge| judge.user).collect::<Vec<_>>();
+    if let Some(access) = admin {
+        let request = PredictionUserRequest {
+            prediction: None,
+            user: None,
+        };
+        // The judges alone are still worth suggesting.
+        if let Ok(bets) = get_bets(request, access).await {
+            users.extend(bets.into_iter().map(|bet| bet.user));
+        }
+    }
+    users.sort();
+    users.dedup();
+    let names = join_all(users.iter().map(|user| get_username(*user))).await;
+    let mut candidates = users
+        .into_iter()
+        .zip(names)
+        .map(|(user, name)| {
+            let judged = judges.iter().filter(|judge| judge.user == user);
+            JudgeCandidate {
+                user,
+                name: name.unwrap_or_default(),
+                nominations: judged.clone().count(),
+                resolved: judged
+                    .filter(|judge| resolved.contains(&judge.prediction))
+                    .count(),
+            }
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| {
+        b.nominations
+            .cmp(&a.nominations)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(candidates)
+}
 pub async fn get_user(user: UserPubKey, access: AccessRequest) -> Result<UserResponse, String> {
     let user = client().get_user(user, access).await.map_err(map_any_err)?;
     Ok(user)
@@ -158,6 +743,23 @@ pub async fn new_prediction(request: NewPredictionRequest) -> Result<RowId, Stri
 pub async fn add_bet(request: AddBetRequest, access: AccessRequest) -> Result<Payment, String> {
     client().add_bet(request, access).await.map_err(map_any_err)
 }
+/// Places the bets one after another, see `one_by_one`.
+pub async fn place_bets(
+    items: Vec<BasketItem>,
+    access: AccessRequest,
+    progress: impl Fn(usize),
+) -> Vec<(BasketItem, Result<Payment, String>)> {
+    let place = move |item: BasketItem| {
+        let request = AddBetRequest {
+            prediction: item.prediction,
+            bet: item.bet,
+            user: access.user,
+            amount: item.amount,
+        };
+        add_bet(request, access.clone())
+    };
+    one_by_one(items, place, progress).await
+}
 pub async fn cancel_bet(id: RowId, access: AccessRequest) -> Result<(), String> {
     client().cancel_bet(id, access).await.map_err(map_any_err)
 }
@@ -198,6 +800,133 @@ pub async fn get_balances_for(access: AccessRequest) -> Result<(Sats, Sats), Str
     let available_balance = get_available_balance(access.clone().user, access).await?;
     Ok((available_balance, balance))
 }
+/// `ledger` is the user's ledger, see `get_ledger`.
+pub async fn get_balance_breakdown(
+    user: UserPubKey,
+    ledger: Vec<LedgerEntry>,
+    access: AccessRequest,
+) -> Result<BalanceParts, String> {
+    let (available, total) = get_balances_for_user(user, access.clone()).await?;
+    let request = PredictionUserRequest {
+        prediction: None,
+        user: Some(user),
+    };
+    let bets = get_bets(request, access.clone()).await?;
+    let predictions = get_prediction_overviews(bets.iter().map(|bet| bet.prediction)).await?;
+    let bets = bets
+        .into_iter()
+        .filter_map(|bet| {
+            let prediction = predictions.get(&bet.prediction)?.clone();
+            is_open(&prediction.state).then_some((bet, prediction))
+        })
+        .collect();
+    let withdrawals = ledger
+        .into_iter()
+        .filter(|entry| {
+            entry.direction == TxDirection::Withdrawal
+                && Settlement::of(&entry.tx) == Settlement::Pending
+        })
+        .collect();
+    Ok(BalanceParts {
+        available,
+        total,
+        bets,
+        withdrawals,
+    })
+}
+/// Available balance and the sum of withdrawals that haven't failed within the last 24 hours.
+pub async fn get_withdrawal_context(
+    user: UserPubKey,
+    access: AccessRequest,
+) -> Result<(Sats, Sats), String> {
+    let available = get_available_balance(user, access.clone()).await?;
+    let since = Utc::now() - Duration::hours(24);
+    let withdrawn = get_ledger(user, access)
+        .await?
+        .into_iter()
+        .filter(|entry| {
+            entry.direction == TxDirection::Withdrawal
+                && entry.tx.created > since
+                && Settlement::of(&entry.tx) != Settlement::Failed
+        })
+        .map(|entry| entry.tx.amount)
+        .sum();
+    Ok((available, withdrawn))
+}
+/// Rebuilds the balance over time from the user's `ledger` and bets.
+/// Bets carry no timestamp, so settled bets are applied when their decision
+/// period ends and open stakes are locked at the current point.
+pub async fn get_balance_history(
+    user: UserPubKey,
+    ledger: Vec<LedgerEntry>,
+    access: AccessRequest,
+) -> Result<Vec<BalancePoint>, String> {
+    let mut changes: Vec<(DateTime<Utc>, i64, i64)> = vec![];
+    for entry in ledger {
+        let amount = entry.tx.amount as i64;
+        match (&entry.direction, Settlement::of(&entry.tx)) {
+            (TxDirection::Deposit, Settlement::Settled) => {
+                changes.push((entry.tx.created, amount, amount))
+            }
+            (TxDirection::Withdrawal, Settlement::Settled) => {
+                changes.push((entry.tx.created, -amount, -amount))
+            }
+            (TxDirection::Withdrawal, Settlement::Pending) => {
+                changes.push((entry.tx.created, -amount, 0))
+            }
+            _ => {}
+        }
+    }
+    let request = PredictionUserRequest {
+        prediction: None,
+        user: Some(user),
+    };
+    let bets = get_bets(request, access).await?;
+    let predictions = get_prediction_overviews(bets.iter().map(|bet| bet.prediction)).await?;
+    for bet in bets {
+        let prediction = &predictions[&bet.prediction];
+        let amount = bet.amount as i64;
+        match prediction.state {
+            MarketState::Resolved(outcome) => {
+                let change = if outcome == bet.bet {
+                    payout(
+                        bet.amount,
+                        bet.bet,
+                        prediction.ratio,
+                        prediction.judge_share_ppm,
+                    ) as i64
+                        - amount
+                } else {
+                    -amount
+                };
+                let resolved = prediction.trading_end
+                    + Duration::seconds(prediction.decision_period_sec as i64);
+                changes.push((resolved, change, change));
+            }
+            ref state if is_open(state) => changes.push((Utc::now(), -amount, 0)),
+            _ => {}
+        }
+    }
+    changes.sort_by_key(|(time, _, _)| *time);
+    let mut history = vec![];
+    let (mut available, mut total) = (0, 0);
+    for (time, available_change, total_change) in changes {
+        available += available_change;
+        total += total_change;
+        history.push(BalancePoint {
+            time,
+            available,
+            total,
+        });
+    }
+    if let Some(last) = history.last().cloned() {
+        history.push(BalancePoint {
+            time: Utc::now().max(last.time),
+            ..last
+        });
+    }
+    Ok(history)
+}
 pub async fn make_deposit_bolt11(
     request: DepositRequest,
     access: AccessRequest,
@@ -222,6 +951,92 @@ pub async fn get_txs(request: TxsRequest, access: AccessRequest) -> Result<Vec<R
 pub async fn get_tx(id: RowId, access: AccessRequest) -> Result<Tx, String> {
     client().check_tx(id, access).await.map_err(map_any_err)
 }
+/// Deposits and withdrawals of a user, newest first.
+pub async fn get_ledger(
+    user: UserPubKey,
+    access: AccessRequest,
+) -> Result<Vec<LedgerEntry>, String> {
+    let mut ledger = vec![];
+    for direction in [TxDirection::Deposit, TxDirection::Withdrawal] {
+        let request = TxsRequest {
+            user: Some(user),
+            direction: Some(direction.clone()),
+        };
+        let ids = get_txs(request, access.clone()).await?;
+        let txs = try_join_all(ids.iter().map(|id| get_tx(*id, access.clone()))).await?;
+        ledger.extend(ids.into_iter().zip(txs).map(|(id, tx)| LedgerEntry {
+            id,
+            direction: direction.clone(),
+            tx,
+        }));
+    }
+    ledger.sort_by(|a, b| b.tx.created.cmp(&a.tx.created));
+    Ok(ledger)
+}
+pub async fn wait_for_settlement(id: RowId, access: AccessRequest) -> Result<Tx, String> {
+    wait_for_settlement_within(id, access, SETTLEMENT_POLLS).await
+}
+/// Like `wait_for_settlement`, but long enough for an invoice to be paid
+/// from a wallet outside the browser.
+pub async fn wait_for_deposit(id: RowId, access: AccessRequest) -> Result<Tx, String> {
+    wait_for_settlement_within(id, access, DEPOSIT_POLLS).await
+}
+async fn wait_for_settlement_within(
+    id: RowId,
+    access: AccessRequest,
+    polls: u32,
+) -> Result<Tx, String> {
+    for _ in 0..polls {
+        let tx = get_tx(id, access.clone()).await?;
+        match Settlement::of(&tx) {
+            Settlement::Settled => return Ok(tx),
+            Settlement::Failed => return Err("Payment failed".to_string()),
+            Settlement::Pending => {}
+        }
+        sleep(SETTLEMENT_POLL_INTERVAL_MS).await;
+    }
+    Err("Payment was sent but isn't settled yet".to_string())
+}
+/// The user's transaction for `invoice`, for payments like bets whose
+/// transaction id isn't returned. Searched newest first.
+pub async fn find_tx(invoice: &str, access: AccessRequest) -> Result<RowId, String> {
+    let request = TxsRequest {
+        user: Some(access.user),
+        direction: None,
+    };
+    for id in get_txs(request, access.clone()).await?.into_iter().rev() {
+        let TxType::Bolt11 { details, .. } = get_tx(id, access.clone()).await?.tx_type;
+        if details.payment_request == invoice {
+            return Ok(id);
+        }
+    }
+    Err("No transaction found for this invoice".to_string())
+}
+/// Pays `invoice` and waits until its transaction settles. Without a `tx` id
+/// the transaction is looked up by the invoice.
+pub async fn pay_with_webln(
+    invoice: Invoice,
+    tx: Option<RowId>,
+    access: AccessRequest,
+) -> Result<String, String> {
+    let preimage = webln::send_payment(invoice.clone()).await?;
+    let tx = match tx {
+        Some(tx) => tx,
+        None => find_tx(invoice.as_str(), access.clone())
+            .await
+            .map_err(|e| {
+                format!(
+                    "Payment sent (preimage {}) but not confirmed: {}",
+                    preimage, e
+                )
+            })?,
+    };
+    wait_for_settlement(tx, access).await?;
+    Ok("Payment settled".to_string())
+}
+pub async fn make_invoice_webln(amount: Sats) -> Result<Invoice, String> {
+    webln::make_invoice(amount).await
+}
 pub async fn force_decision_period(prediction: RowId, access: AccessRequest) -> Result<(), String> {
     client()
         .force_decision_period(prediction, access)
@@ -234,3 +1049,68 @@ where
 {
     signal.get_untracked()
 }
+pub async fn sleep(ms: i32) {
+    let promise = Promise::new(&mut |resolve, _| {
+        if window()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms)
+            .is_err()
+        {
+            log::error!("couldn't set timeout");
+        }
+    });
+    let _ = JsFuture::from(promise).await;
+}
+/// GET request to a third party (not the Mercado server) expecting JSON.
+pub async fn fetch_json(url: &str) -> Result<serde_json::Value, String> {
+    let map_js_err = |error: JsValue| format!("{:?}", error);
+    let response = JsFuture::from(window().fetch_with_str(url))
+        .await
+        .map_err(map_js_err)?
+        .dyn_into::<Response>()
+        .map_err(map_js_err)?;
+    if !response.ok() {
+        return Err(format!("{} answered with {}", url, response.status()));
+    }
+    let text = JsFuture::from(response.text().map_err(map_js_err)?)
+        .await
+        .map_err(map_js_err)?
+        .as_string()
+        .unwrap_or_default();
+    serde_json::from_str(text.as_str()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(on_true: Sats, on_false: Sats) -> Position {
+        Position {
+            bets: vec![],
+            on_true,
+            on_false,
+        }
+    }
+
+    #[test]
+    fn payout_shares_the_pot_after_the_judge_share() {
+        assert_eq!(payout(100, true, (100, 100), 0), 200);
+        assert_eq!(payout(100, true, (100, 100), 100_000), 180);
+        assert_eq!(payout(50, false, (300, 100), 0), 200);
+        assert_eq!(payout(100, true, (0, 100), 0), 100);
+    }
+    #[test]
+    fn net_subtracts_the_stake_on_both_sides() {
+        let hedged = position(100, 50);
+        assert_eq!(hedged.stake(), 150);
+        assert_eq!(hedged.net(true, (200, 200), 0), 50);
+        assert_eq!(hedged.net(false, (200, 200), 0), -50);
+        assert_eq!(position(100, 0).net(false, (100, 100), 0), -100);
+        assert_eq!(position(100, 0).net(true, (100, 100), 0), 100);
+    }
+    #[test]
+    fn net_is_never_above_the_pot_minus_the_judge_share() {
+        let whole_pot = position(100, 0);
+        assert_eq!(whole_pot.net(true, (100, 0), 0), 0);
+        assert_eq!(whole_pot.net(true, (100, 0), 500_000), -50);
+    }
+}
@@ -0,0 +1,164 @@
+//! Client-side safeguards for withdrawals.
+use crate::sats::{self, Unit};
+use chrono::{DateTime, Duration, Utc};
+use mercado::api::Sats;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawalLimits {
+    /// Withdrawals above this amount need re-authentication.
+    pub confirm_above: Sats,
+    /// Withdrawals above this share of the available balance need re-authentication.
+    pub confirm_above_percent: u8,
+    /// Maximum withdrawn within 24 hours. 0 disables the cap.
+    pub daily_cap: Sats,
+    /// Withdrawals are blocked for this long after logging in.
+    pub cooldown_minutes: u32,
+}
+impl Default for WithdrawalLimits {
+    fn default() -> Self {
+        Self {
+            confirm_above: 100_000,
+            confirm_above_percent: 50,
+            daily_cap: 1_000_000,
+            cooldown_minutes: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Confirm(String),
+    Block(String),
+}
+
+impl WithdrawalLimits {
+    pub fn check(
+        &self,
+        amount: Sats,
+        available: Sats,
+        withdrawn_today: Sats,
+        session_start: Option<DateTime<Utc>>,
+        unit: Unit,
+    ) -> Verdict {
+        // A session of unknown age counts as one that just started.
+        let now = Utc::now();
+        let ready = session_start.unwrap_or(now) + Duration::minutes(self.cooldown_minutes as i64);
+        if now < ready {
+            return Verdict::Block(format!(
+                "Withdrawals are paused for {} more minute(s) after logging in",
+                (ready - now).num_minutes() + 1
+            ));
+        }
+        if self.daily_cap > 0 && withdrawn_today as u128 + amount as u128 > self.daily_cap as u128 {
+            return Verdict::Block(format!(
+                "This exceeds the daily limit of {}. Withdrawn in the last 24 hours: {}",
+                sats::format(self.daily_cap, unit),
+                sats::format(withdrawn_today, unit),
+            ));
+        }
+        if amount > self.confirm_above {
+            return Verdict::Confirm(format!(
+                "Withdrawals above {} need confirmation",
+                sats::format(self.confirm_above, unit)
+            ));
+        }
+        if amount as u128 * 100 > available as u128 * self.confirm_above_percent as u128 {
+            return Verdict::Confirm(format!(
+                "Withdrawing more than {}% of the available balance needs confirmation",
+                self.confirm_above_percent
+            ));
+        }
+        Verdict::Allow
+    }
+    /// Whether any of the limits lets through something `current` wouldn't.
+    pub fn is_looser_than(&self, current: &WithdrawalLimits) -> bool {
+        let cap = |cap: Sats| if cap == 0 { u128::MAX } else { cap as u128 };
+        self.confirm_above > current.confirm_above
+            || self.confirm_above_percent > current.confirm_above_percent
+            || cap(self.daily_cap) > cap(current.daily_cap)
+            || self.cooldown_minutes < current.cooldown_minutes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn long_ago() -> Option<DateTime<Utc>> {
+        Some(Utc::now() - Duration::days(1))
+    }
+    fn check(amount: Sats, available: Sats, withdrawn_today: Sats) -> Verdict {
+        WithdrawalLimits::default().check(
+            amount,
+            available,
+            withdrawn_today,
+            long_ago(),
+            Unit::Sats,
+        )
+    }
+
+    #[test]
+    fn allows_small_withdrawals() {
+        assert_eq!(check(1_000, 1_000_000, 0), Verdict::Allow);
+    }
+    #[test]
+    fn confirms_large_amounts_and_shares() {
+        assert!(matches!(check(100_001, 1_000_000, 0), Verdict::Confirm(_)));
+        assert!(matches!(check(600, 1_000, 0), Verdict::Confirm(_)));
+        assert_eq!(check(500, 1_000, 0), Verdict::Allow);
+    }
+    #[test]
+    fn blocks_above_daily_cap() {
+        assert!(matches!(check(1, 1_000_000, 1_000_000), Verdict::Block(_)));
+        assert_eq!(check(1_000, 1_000_000, 999_000), Verdict::Allow);
+        let uncapped = WithdrawalLimits {
+            daily_cap: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            uncapped.check(1_000, 1_000_000, Sats::MAX, long_ago(), Unit::Sats),
+            Verdict::Allow
+        );
+    }
+    #[test]
+    fn blocks_during_cooldown() {
+        let limits = WithdrawalLimits::default();
+        let just_now = Some(Utc::now());
+        assert!(matches!(
+            limits.check(1, 1_000, 0, just_now, Unit::Sats),
+            Verdict::Block(_)
+        ));
+        assert!(matches!(
+            limits.check(1, 1_000, 0, None, Unit::Sats),
+            Verdict::Block(_)
+        ));
+        let no_cooldown = WithdrawalLimits {
+            cooldown_minutes: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            no_cooldown.check(1, 1_000, 0, None, Unit::Sats),
+            Verdict::Allow
+        );
+    }
+    #[test]
+    fn detects_looser_limits() {
+        let current = WithdrawalLimits::default();
+        assert!(!current.is_looser_than(&current));
+        let stricter = WithdrawalLimits {
+            confirm_above: 1_000,
+            cooldown_minutes: 60,
+            ..Default::default()
+        };
+        assert!(!stricter.is_looser_than(&current));
+        assert!(current.is_looser_than(&stricter));
+        let uncapped = WithdrawalLimits {
+            daily_cap: 0,
+            ..Default::default()
+        };
+        assert!(uncapped.is_looser_than(&current));
+        assert!(!current.is_looser_than(&uncapped));
+    }
+}
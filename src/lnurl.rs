@@ -0,0 +1,156 @@
+//! Turns Lightning Addresses and LNURL-pay codes into Bolt11 invoices
+//! (LUD-06 and LUD-16), so they can be used as withdrawal destinations.
+use crate::fetchers::fetch_json;
+use bech32::FromBase32;
+use mercado::api::{Invoice, Sats};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    Invoice(Invoice),
+    LightningAddress { user: String, domain: String },
+    Lnurl(String),
+}
+impl Destination {
+    pub fn parse(destination: &str) -> Result<Self, String> {
+        let destination = destination.trim();
+        let destination = destination
+            .strip_prefix("lightning:")
+            .unwrap_or(destination);
+        let lowercase = destination.to_lowercase();
+        if lowercase.starts_with("lnurl") {
+            let (hrp, data, _) = bech32::decode(lowercase.as_str()).map_err(|e| e.to_string())?;
+            if hrp != "lnurl" {
+                return Err("Not an LNURL".to_string());
+            }
+            let url = Vec::<u8>::from_base32(&data).map_err(|e| e.to_string())?;
+            let url = String::from_utf8(url).map_err(|e| e.to_string())?;
+            Ok(Self::Lnurl(url))
+        } else if lowercase.starts_with("lnbc") || lowercase.starts_with("lntb") {
+            Ok(Self::Invoice(destination.to_string()))
+        } else if let Some((user, domain)) = destination.split_once('@') {
+            if user.is_empty() || domain.is_empty() || domain.contains('/') {
+                return Err("Not a valid Lightning Address".to_string());
+            }
+            Ok(Self::LightningAddress {
+                user: user.to_string(),
+                domain: domain.to_string(),
+            })
+        } else {
+            Err("Expected a Lightning Address, LNURL or invoice".to_string())
+        }
+    }
+}
+
+/// Amount in msats encoded in a Bolt11 invoice's human readable part, `None`
+/// for invoices without an amount.
+pub fn invoice_msats(invoice: &str) -> Result<Option<u64>, String> {
+    let invoice = invoice.trim().to_lowercase();
+    let invoice = invoice
+        .strip_prefix("lightning:")
+        .unwrap_or(invoice.as_str());
+    let invalid = || "Not a valid invoice".to_string();
+    let (hrp, _) = invoice.rsplit_once('1').ok_or_else(invalid)?;
+    let amount = hrp
+        .strip_prefix("ln")
+        .ok_or_else(invalid)?
+        .trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    if amount.is_empty() {
+        return Ok(None);
+    }
+    let (digits, multiplier) = match amount.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&amount[..i], Some(c)),
+        _ => (amount, None),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let digits = digits.parse::<u64>().map_err(|_| invalid())?;
+    // One BTC is 10^11 msats.
+    let msats = match multiplier {
+        None => digits.checked_mul(100_000_000_000),
+        Some('m') => digits.checked_mul(100_000_000),
+        Some('u') => digits.checked_mul(100_000),
+        Some('n') => digits.checked_mul(100),
+        Some('p') if digits % 10 == 0 => Some(digits / 10),
+        _ => None,
+    };
+    msats.map(Some).ok_or_else(invalid)
+}
+
+/// Returns an invoice over `amount` for the destination.
+pub async fn resolve_invoice(destination: &str, amount: Sats) -> Result<Invoice, String> {
+    let url = match Destination::parse(destination)? {
+        Destination::Invoice(invoice) => return Ok(invoice),
+        Destination::LightningAddress { user, domain } => {
+            format!("https://{}/.well-known/lnurlp/{}", domain, user)
+        }
+        Destination::Lnurl(url) => url,
+    };
+    let pay = fetch_json(url.as_str()).await?;
+    if let Some(reason) = pay.get("reason").and_then(|reason| reason.as_str()) {
+        return Err(reason.to_string());
+    }
+    let msats = amount as u64 * 1000;
+    let min = pay["minSendable"].as_u64().unwrap_or(0);
+    let max = pay["maxSendable"].as_u64().unwrap_or(u64::MAX);
+    if msats < min || msats > max {
+        return Err(format!(
+            "The destination accepts {} to {} sats",
+            min / 1000,
+            max / 1000
+        ));
+    }
+    let callback = pay["callback"]
+        .as_str()
+        .ok_or("The destination didn't send a callback".to_string())?;
+    let separator = if callback.contains('?') { '&' } else { '?' };
+    let invoice = fetch_json(format!("{}{}amount={}", callback, separator, msats).as_str()).await?;
+    if let Some(reason) = invoice.get("reason").and_then(|reason| reason.as_str()) {
+        return Err(reason.to_string());
+    }
+    let invoice = invoice["pr"]
+        .as_str()
+        .ok_or("The destination didn't send an invoice".to_string())?;
+    // LUD-06: the invoice has to be over exactly the requested amount.
+    if invoice_msats(invoice)? != Some(msats) {
+        return Err("The destination sent an invoice over a different amount".to_string());
+    }
+    Ok(invoice.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_invoice_amounts() {
+        assert_eq!(invoice_msats("lnbc1pvjluezpp5qqqsyq"), Ok(None));
+        assert_eq!(invoice_msats("lnbc2500u1pvjluez"), Ok(Some(250_000_000)));
+        assert_eq!(invoice_msats("lnbc20m1pvjluez"), Ok(Some(2_000_000_000)));
+        assert_eq!(invoice_msats("LNTB10N1PVJLUEZ"), Ok(Some(1_000)));
+        assert_eq!(invoice_msats("lnbcrt1230p1pvjluez"), Ok(Some(123)));
+        assert_eq!(
+            invoice_msats("lightning:lnbc1m1pvjluez"),
+            Ok(Some(100_000_000))
+        );
+        assert!(invoice_msats("lnbc1231p1pvjluez").is_err());
+        assert!(invoice_msats("lnbc12x1pvjluez").is_err());
+        assert!(invoice_msats("bc1qxyz").is_err());
+    }
+    #[test]
+    fn parses_destinations() {
+        assert_eq!(
+            Destination::parse("lightning:alice@example.com"),
+            Ok(Destination::LightningAddress {
+                user: "alice".to_string(),
+                domain: "example.com".to_string()
+            })
+        );
+        assert!(matches!(
+            Destination::parse("lnbc2500u1pvjluez"),
+            Ok(Destination::Invoice(_))
+        ));
+        assert!(Destination::parse("alice@").is_err());
+        assert!(Destination::parse("alice").is_err());
+    }
+}
@@ -6,8 +6,21 @@ use leptos_router::*;
 use mercado::api::{AccessRequest, UserResponse};
 use serde::{Deserialize, Serialize};
 
+mod address_book;
+mod basket;
+mod chart;
 mod components;
+mod created;
+mod drafts;
+mod export;
 mod fetchers;
+mod limits;
+mod lnurl;
+mod price;
+mod sats;
+mod storage;
+mod validation;
+mod webln;
 
 const URL: &str = "http://127.0.0.1:8081";
 const STORAGE_KEY: &str = "mercado-state";
@@ -16,51 +29,88 @@ const STORAGE_KEY: &str = "mercado-state";
 pub struct MercadoState {
     access: Option<AccessRequest>,
     user: Option<UserResponse>,
+    #[serde(default)]
+    unit: sats::Unit,
+    #[serde(default)]
+    price_feed: Option<price::PriceFeed>,
+    #[serde(default)]
+    price: Option<price::Price>,
+    #[serde(default)]
+    session_start: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    withdrawal_limits: limits::WithdrawalLimits,
 }
 
 fn main() {
     mount_to_body(|| {
-        let (state, set_state) = create_signal::<MercadoState>(MercadoState {
-            access: None,
-            user: None,
-        });
-        let storage_access = window()
-            .local_storage()
-            .ok()
-            .flatten()
-            .and_then(|storage| {
-                storage
-                    .get_item(STORAGE_KEY)
-                    .ok()
-                    .flatten()
-                    .and_then(|value| serde_json::from_str::<MercadoState>(&value).ok())
-            })
-            .unwrap_or_default();
-        set_state.set(storage_access);
+        let (state, set_state) =
+            create_signal::<MercadoState>(storage::load(STORAGE_KEY).unwrap_or_default());
+        create_effect(move |_| storage::save(STORAGE_KEY, &state.get()));
+        // Sessions stored before withdrawal limits existed have no start;
+        // the cooldown for them begins now.
         create_effect(move |_| {
-            if let Ok(Some(storage)) = window().local_storage() {
-                let json =
-                    serde_json::to_string(&state.get()).expect("couldn't serialize AccessRequest");
-                if storage.set_item(STORAGE_KEY, &json).is_err() {
-                    log::error!("error while trying to set item in localStorage");
+            if state.with(|state| state.access.is_some() && state.session_start.is_none()) {
+                set_state.update(|state| state.session_start = Some(chrono::Utc::now()));
+            }
+        });
+        // The feed being fetched, so a slow feed isn't queried again by the
+        // interval before it answered. A changed feed is fetched right away
+        // and the answer from the old one is dropped.
+        let fetching_price = store_value::<Option<price::PriceFeed>>(None);
+        let refresh_price = move || {
+            let MercadoState {
+                price_feed, price, ..
+            } = state.get_untracked();
+            if let Some(feed) = price_feed {
+                if fetching_price.get_value().as_ref() != Some(&feed)
+                    && price.map_or(true, |price| price.needs_refresh(&feed))
+                {
+                    fetching_price.set_value(Some(feed.clone()));
+                    spawn_local(async move {
+                        let result = price::fetch_price(feed.clone()).await;
+                        if fetching_price.get_value().as_ref() != Some(&feed) {
+                            return;
+                        }
+                        fetching_price.set_value(None);
+                        match result {
+                            Ok(price) => set_state.update(|state| {
+                                if state.price_feed.as_ref() == Some(&price.feed) {
+                                    state.price = Some(price);
+                                }
+                            }),
+                            Err(e) => log::error!("{}", e),
+                        }
+                    });
                 }
             }
+        };
+        let price_feed = create_memo(move |_| state.with(|state| state.price_feed.clone()));
+        create_effect(move |_| {
+            price_feed.track();
+            refresh_price();
         });
+        set_interval(refresh_price, std::time::Duration::from_secs(60));
 
         view! {
             <Root default_theme=LeptonicTheme::Dark>
                 <Router>
                     <Navi state=state set_state=set_state />
+                    <WalletBanner state=state />
                     <Box style="padding-left: 20px; padding-right: 20px;"><Routes>
-                        <Route path="" view=App/>
+                        <Route path="" view=move || view! {<App state=state />}/>
                         <Route path="prediction" view=move || view! {<Outlet/>}>
                             <Route path=":id" view=move || view! {<PredictionOverview state=state />}/>
-                            <Route path="" view=App/>
+                            <Route path="" view=move || view! {<App state=state />}/>
                         </Route>
                         <Route path="login" view=move || view! {<Login set_state=set_state />} />
+                        <Route path="users" view=move || view!{<Users state=state />} />
+                        <Route path="settings" view=move || view!{<Settings state=state set_state=set_state />} />
                         <Route path="my_bets" view=move || view!{<MyBets state=state />} />
                         <Route path="my_judges" view=move || view!{<MyJudges state=state />} />
+                        <Route path="judge/:id" view=move || view!{<JudgeTrackRecord state=state />} />
+                        <Route path="drafts" view=move || view!{<Drafts />} />
                         <Route path="new_prediction" view=move || view!{<NewPrediction state=state />} />
+                        <Route path="basket" view=move || view!{<Basket state=state />} />
                         <Route path="add_bet" view=move || view!{<AddBet state=state />} />
                         <Route path="wallet" view=move || view! {<Outlet/>}>
                             <Route path=":id" view=move || view! {<Wallet state=state />}/>
@@ -70,6 +120,7 @@ fn main() {
                             <Route path=":id" view=move || view! {<MakeDeposit state=state />}/>
                             <Route path="" view=move || view!{<MakeDeposit state=state/>}/>
                         </Route>
+                        <Route path="tx/:id" view=move || view! {<TxDetail state=state />} />
                         <Route path="make_withdrawal" view=move || view! {<Outlet/>}>
                             <Route path=":id" view=move || view! {<MakeWithdrawal state=state />}/>
                             <Route path="" view=move || view!{<MakeWithdrawal state=state/>}/>
@@ -0,0 +1,133 @@
+//! Optional BTC/fiat reference price from a configurable HTTP endpoint.
+//!
+//! The endpoint has to answer a GET request with JSON. `pointer` is a JSON
+//! pointer to the price of one BTC in that response, e.g. `/bitcoin/eur` for
+//! `https://api.coingecko.com/api/v3/simple/price?ids=bitcoin&vs_currencies=eur`
+//! or `/rate` for a local stub serving `{"rate": 50000}`.
+use crate::fetchers::fetch_json;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Prices older than this are refetched.
+pub const REFRESH_SECS: i64 = 5 * 60;
+/// Prices older than this are marked as stale.
+pub const STALE_SECS: i64 = 30 * 60;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PriceFeed {
+    pub url: String,
+    pub pointer: String,
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Price {
+    /// The feed this price came from. Prices stored before it was recorded
+    /// get an empty feed and are refetched.
+    #[serde(default)]
+    pub feed: PriceFeed,
+    pub per_btc: f64,
+    pub fetched: DateTime<Utc>,
+}
+impl Price {
+    pub fn age(&self) -> Duration {
+        Utc::now() - self.fetched
+    }
+    pub fn is_stale(&self) -> bool {
+        self.age() > Duration::seconds(STALE_SECS)
+    }
+    pub fn needs_refresh(&self, feed: &PriceFeed) -> bool {
+        self.feed != *feed || self.age() > Duration::seconds(REFRESH_SECS)
+    }
+    /// Reference value only, so floating point is fine here.
+    pub fn format(&self, sats: i128) -> String {
+        format!(
+            "≈ {:.2} {}",
+            sats as f64 / 100_000_000.0 * self.per_btc,
+            self.feed.currency
+        )
+    }
+}
+
+pub async fn fetch_price(feed: PriceFeed) -> Result<Price, String> {
+    let json = fetch_json(feed.url.as_str())
+        .await
+        .map_err(|e| format!("Price feed: {}", e))?;
+    Ok(Price {
+        per_btc: parse_price(&json, feed.pointer.as_str())?,
+        feed,
+        fetched: Utc::now(),
+    })
+}
+/// The positive price at `pointer`, given as a number or a numeric string.
+fn parse_price(json: &Value, pointer: &str) -> Result<f64, String> {
+    json.pointer(pointer)
+        .and_then(|value| {
+            value
+                .as_f64()
+                .or_else(|| value.as_str().and_then(|value| value.parse().ok()))
+        })
+        .filter(|per_btc: &f64| per_btc.is_finite() && *per_btc > 0.0)
+        .ok_or(format!("Price feed: no price at {}", pointer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn feed(url: &str, pointer: &str, currency: &str) -> PriceFeed {
+        PriceFeed {
+            url: url.to_string(),
+            pointer: pointer.to_string(),
+            currency: currency.to_string(),
+        }
+    }
+    fn price(feed: PriceFeed, age_secs: i64) -> Price {
+        Price {
+            feed,
+            per_btc: 50_000.0,
+            fetched: Utc::now() - Duration::seconds(age_secs),
+        }
+    }
+
+    #[test]
+    fn refreshes_old_prices() {
+        let eur = feed("https://example.com/price", "/rate", "EUR");
+        assert!(!price(eur.clone(), 0).needs_refresh(&eur));
+        assert!(price(eur.clone(), REFRESH_SECS + 1).needs_refresh(&eur));
+    }
+    #[test]
+    fn refreshes_when_any_part_of_the_feed_changes() {
+        let eur = feed("https://example.com/price", "/rate", "EUR");
+        let fresh = price(eur.clone(), 0);
+        assert!(fresh.needs_refresh(&feed("https://example.org/price", "/rate", "EUR")));
+        assert!(fresh.needs_refresh(&feed("https://example.com/price", "/eur", "EUR")));
+        assert!(fresh.needs_refresh(&feed("https://example.com/price", "/rate", "USD")));
+        assert!(price(PriceFeed::default(), 0).needs_refresh(&eur));
+    }
+    #[test]
+    fn parses_numbers_and_numeric_strings() {
+        let json = json!({"bitcoin": {"eur": 50000.5, "usd": "60000"}, "rate": 1});
+        assert_eq!(parse_price(&json, "/bitcoin/eur"), Ok(50000.5));
+        assert_eq!(parse_price(&json, "/bitcoin/usd"), Ok(60000.0));
+        assert_eq!(parse_price(&json, "/rate"), Ok(1.0));
+    }
+    #[test]
+    fn rejects_missing_and_invalid_prices() {
+        let json = json!({"zero": 0, "negative": -1, "nan": "NaN", "text": "high", "list": [1]});
+        for pointer in [
+            "/missing",
+            "/zero",
+            "/negative",
+            "/nan",
+            "/text",
+            "/list",
+            "rate",
+        ] {
+            assert!(parse_price(&json, pointer).is_err(), "{}", pointer);
+        }
+        assert_eq!(parse_price(&json, "/list/0"), Ok(1.0));
+    }
+}
@@ -0,0 +1,184 @@
+//! Exact conversion between sat amounts and their text in sats, mBTC or BTC.
+//! Amounts never pass through floating point.
+use mercado::api::Sats;
+use serde::{Deserialize, Serialize};
+
+/// The largest amount accepted anywhere: 21 million BTC.
+pub const MAX_SATS: u64 = 21_000_000 * 100_000_000;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unit {
+    #[default]
+    Sats,
+    MilliBtc,
+    Btc,
+}
+impl Unit {
+    pub const ALL: [Unit; 3] = [Unit::Sats, Unit::MilliBtc, Unit::Btc];
+    pub fn decimals(&self) -> usize {
+        match self {
+            Unit::Sats => 0,
+            Unit::MilliBtc => 5,
+            Unit::Btc => 8,
+        }
+    }
+    fn sats_per_unit(&self) -> u64 {
+        10u64.pow(self.decimals() as u32)
+    }
+}
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unit::Sats => write!(f, "sats"),
+            Unit::MilliBtc => write!(f, "mBTC"),
+            Unit::Btc => write!(f, "BTC"),
+        }
+    }
+}
+impl std::str::FromStr for Unit {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Unit::ALL
+            .into_iter()
+            .find(|unit| unit.to_string() == s)
+            .ok_or(format!("Unknown unit {}", s))
+    }
+}
+
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+fn split(amount: i128, unit: Unit) -> (String, String, String) {
+    let sign = if amount < 0 { "-" } else { "" };
+    let amount = amount.unsigned_abs();
+    let per_unit = unit.sats_per_unit() as u128;
+    let whole = (amount / per_unit).to_string();
+    let fraction = format!("{:0width$}", amount % per_unit, width = unit.decimals());
+    let fraction = fraction.trim_end_matches('0').to_string();
+    (sign.to_string(), whole, fraction)
+}
+/// Amount with thousands separators and unit, e.g. `1,234.5 mBTC`.
+pub fn format(amount: impl Into<i128>, unit: Unit) -> String {
+    let (sign, whole, fraction) = split(amount.into(), unit);
+    let whole = group_thousands(whole.as_str());
+    if fraction.is_empty() {
+        format!("{}{} {}", sign, whole, unit)
+    } else {
+        format!("{}{}.{} {}", sign, whole, fraction, unit)
+    }
+}
+/// Amount as it should appear in an input field, without separators or unit.
+pub fn to_input(amount: Sats, unit: Unit) -> String {
+    let (sign, whole, fraction) = split(amount as i128, unit);
+    if fraction.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+/// Parses a non-negative amount in `unit`. Thousands separators are ignored,
+/// fractions of a sat are rejected.
+pub fn parse(input: &str, unit: Unit) -> Result<Sats, String> {
+    let input = input.trim().replace([',', '_', ' '], "");
+    let (whole, fraction) = input.split_once('.').unwrap_or((input.as_str(), ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err("Enter an amount".to_string());
+    }
+    if !whole
+        .chars()
+        .chain(fraction.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err("Only digits and one decimal point are allowed".to_string());
+    }
+    if fraction.len() > unit.decimals() {
+        return Err(match unit {
+            Unit::Sats => "Amounts in sats can't have decimals".to_string(),
+            _ => format!("{} has at most {} decimal places", unit, unit.decimals()),
+        });
+    }
+    let too_large = || {
+        format!(
+            "Amounts above {} aren't possible",
+            format(MAX_SATS, Unit::Btc)
+        )
+    };
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<u64>().map_err(|_| too_large())?
+    };
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", fraction, width = unit.decimals())
+            .parse::<u64>()
+            .map_err(|_| too_large())?
+    };
+    let sats = whole
+        .checked_mul(unit.sats_per_unit())
+        .and_then(|whole| whole.checked_add(fraction))
+        .filter(|sats| *sats <= MAX_SATS)
+        .ok_or_else(too_large)?;
+    Sats::try_from(sats).map_err(|_| too_large())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_with_separators_and_unit() {
+        assert_eq!(format(0u64, Unit::Sats), "0 sats");
+        assert_eq!(format(1_234_567u64, Unit::Sats), "1,234,567 sats");
+        assert_eq!(format(123_450_000u64, Unit::MilliBtc), "1,234.5 mBTC");
+        assert_eq!(format(100_000_000u64, Unit::Btc), "1 BTC");
+        assert_eq!(format(1u64, Unit::Btc), "0.00000001 BTC");
+        assert_eq!(format(-1_500i64, Unit::Sats), "-1,500 sats");
+    }
+    #[test]
+    fn input_text_has_no_separators() {
+        assert_eq!(to_input(1_234_567, Unit::Sats), "1234567");
+        assert_eq!(to_input(150_000, Unit::MilliBtc), "1.5");
+        assert_eq!(to_input(1, Unit::Btc), "0.00000001");
+    }
+    #[test]
+    fn parses_each_unit() {
+        assert_eq!(parse("1,234", Unit::Sats), Ok(1_234));
+        assert_eq!(parse(" 1 000 ", Unit::Sats), Ok(1_000));
+        assert_eq!(parse("1.5", Unit::MilliBtc), Ok(150_000));
+        assert_eq!(parse(".5", Unit::MilliBtc), Ok(50_000));
+        assert_eq!(parse("0.00000001", Unit::Btc), Ok(1));
+        assert_eq!(
+            parse("21000000", Unit::Btc).map(|sats| sats as u64),
+            Ok(MAX_SATS)
+        );
+    }
+    #[test]
+    fn round_trips_through_input_text() {
+        for unit in Unit::ALL {
+            for amount in [0, 1, 99_999, 123_456_789] {
+                assert_eq!(parse(to_input(amount, unit).as_str(), unit), Ok(amount));
+            }
+        }
+    }
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(parse("", Unit::Sats).is_err());
+        assert!(parse(".", Unit::Sats).is_err());
+        assert!(parse("-1", Unit::Sats).is_err());
+        assert!(parse("1e3", Unit::Sats).is_err());
+        assert!(parse("1.2.3", Unit::Btc).is_err());
+        assert!(parse("1.5", Unit::Sats).is_err());
+        assert!(parse("0.000000001", Unit::Btc).is_err());
+        assert!(parse("21000000.00000001", Unit::Btc).is_err());
+        assert!(parse("99999999999999999999", Unit::Sats).is_err());
+    }
+}
@@ -0,0 +1,20 @@
+//! JSON values in the browser's localStorage.
+use leptos::window;
+use serde::{de::DeserializeOwned, Serialize};
+
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    window()
+        .local_storage()
+        .ok()
+        .flatten()
+        .and_then(|storage| storage.get_item(key).ok().flatten())
+        .and_then(|value| serde_json::from_str::<T>(&value).ok())
+}
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    if let Ok(Some(storage)) = window().local_storage() {
+        let json = serde_json::to_string(value).expect("couldn't serialize value for localStorage");
+        if storage.set_item(key, &json).is_err() {
+            log::error!("error while trying to set item in localStorage");
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Checks a `NewPredictionRequest` field by field before it's sent, and maps
+//! errors from the server back onto the field they concern.
+use chrono::{DateTime, Utc};
+use mercado::api::{NewPredictionRequest, UserPubKey};
+
+/// The judges' share can't exceed the whole pot.
+pub const MAX_JUDGE_SHARE_PPM: u32 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    TradingEnd,
+    DecisionPeriod,
+    JudgeShare,
+    JudgeCount,
+    Judges,
+}
+
+/// A decision period entered in hours or days, in seconds. `None` if it
+/// doesn't fit into a request.
+pub fn decision_period_sec(amount: f64, in_days: bool) -> Option<u32> {
+    let unit: u64 = if in_days { 86400 } else { 3600 };
+    (amount.max(0.0) as u64)
+        .checked_mul(unit)
+        .filter(|sec| *sec <= u32::MAX as u64)
+        .map(|sec| sec as u32)
+}
+
+/// Checks the whole request. Also covers what the judge picker prevents, since
+/// drafts, duplicates and re-proposals fill in the judges without it.
+/// `decision_period_sec` is the entered period, see [`decision_period_sec`].
+pub fn validate(
+    request: &NewPredictionRequest,
+    decision_period_sec: Option<u32>,
+    now: DateTime<Utc>,
+    me: Option<UserPubKey>,
+) -> Vec<(Field, String)> {
+    let mut errors = vec![];
+    if request.prediction.trim().is_empty() {
+        errors.push((Field::Name, "Enter the question to predict".to_string()));
+    }
+    if request.trading_end <= now {
+        errors.push((
+            Field::TradingEnd,
+            "Trading has to end in the future".to_string(),
+        ));
+    }
+    if decision_period_sec.is_none() {
+        errors.push((
+            Field::DecisionPeriod,
+            format!(
+                "The decision period can be at most {} days",
+                u32::MAX / 86400
+            ),
+        ));
+    } else if request.decision_period_sec == 0 {
+        errors.push((
+            Field::DecisionPeriod,
+            "Judges need some time to decide".to_string(),
+        ));
+    }
+    if request.judge_share_ppm > MAX_JUDGE_SHARE_PPM {
+        errors.push((
+            Field::JudgeShare,
+            format!(
+                "The judges can get at most {} ppm (100%)",
+                MAX_JUDGE_SHARE_PPM
+            ),
+        ));
+    }
+    if request.judge_count == 0 {
+        errors.push((
+            Field::JudgeCount,
+            "At least one judge has to participate".to_string(),
+        ));
+    } else if request.judge_count as usize > request.judges.len() {
+        errors.push((
+            Field::JudgeCount,
+            format!(
+                "{} judges have to participate but only {} are listed",
+                request.judge_count,
+                request.judges.len()
+            ),
+        ));
+    }
+    for (i, judge) in request.judges.iter().enumerate() {
+        if request.judges[..i].contains(judge) {
+            errors.push((Field::Judges, format!("{} is listed twice", judge)));
+        }
+    }
+    if me.is_some_and(|me| request.judges.contains(&me)) {
+        errors.push((
+            Field::Judges,
+            "You can't judge your own prediction".to_string(),
+        ));
+    }
+    errors
+}
+
+/// Whether `judge` can be added to the already listed `judges`.
+pub fn check_judge(
+    judge: UserPubKey,
+    judges: &[UserPubKey],
+    me: Option<UserPubKey>,
+) -> Result<(), String> {
+    if judges.contains(&judge) {
+        Err("Already listed as a judge".to_string())
+    } else if me == Some(judge) {
+        Err("You can't judge your own prediction".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Best guess at which field a server error is about. Errors that never
+/// reached the server aren't about any field.
+pub fn field_of(error: &str) -> Option<Field> {
+    let error = error.to_lowercase();
+    let mentions = |words: &[&str]| words.iter().any(|word| error.contains(word));
+    if mentions(&[
+        "http://",
+        "https://",
+        "error sending request",
+        "failed to fetch",
+        "networkerror",
+    ]) {
+        None
+    } else if mentions(&["judge_count", "judge count"]) {
+        Some(Field::JudgeCount)
+    } else if mentions(&["share", "ppm"]) {
+        Some(Field::JudgeShare)
+    } else if mentions(&["trading_end", "trading end", "end date", "in the past"]) {
+        Some(Field::TradingEnd)
+    } else if mentions(&["decision"]) {
+        Some(Field::DecisionPeriod)
+    } else if mentions(&["judge"]) {
+        Some(Field::Judges)
+    } else if mentions(&["name"]) {
+        Some(Field::Name)
+    } else {
+        None
+    }
+}
+
+pub fn errors_for(errors: &[(Field, String)], field: Field) -> Vec<String> {
+    errors
+        .iter()
+        .filter(|(other, _)| *other == field)
+        .map(|(_, error)| error.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const ALICE: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const BOB: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+    fn user(key: &str) -> UserPubKey {
+        key.parse().unwrap()
+    }
+    fn request(now: DateTime<Utc>) -> NewPredictionRequest {
+        NewPredictionRequest {
+            prediction: "Will it rain tomorrow?".to_string(),
+            judges: vec![user(ALICE), user(BOB)],
+            judge_count: 2,
+            judge_share_ppm: 10_000,
+            trading_end: now + Duration::days(1),
+            decision_period_sec: 3600,
+        }
+    }
+    fn fields(errors: Vec<(Field, String)>) -> Vec<Field> {
+        errors.into_iter().map(|(field, _)| field).collect()
+    }
+    fn check(
+        request: &NewPredictionRequest,
+        now: DateTime<Utc>,
+        me: Option<UserPubKey>,
+    ) -> Vec<Field> {
+        fields(validate(
+            request,
+            Some(request.decision_period_sec),
+            now,
+            me,
+        ))
+    }
+
+    #[test]
+    fn accepts_valid_request() {
+        let now = Utc::now();
+        assert!(check(&request(now), now, None).is_empty());
+    }
+    #[test]
+    fn reports_each_field() {
+        let now = Utc::now();
+        let request = NewPredictionRequest {
+            prediction: "  ".to_string(),
+            trading_end: now,
+            decision_period_sec: 0,
+            judge_share_ppm: MAX_JUDGE_SHARE_PPM + 1,
+            judge_count: 0,
+            ..request(now)
+        };
+        assert_eq!(
+            check(&request, now, None),
+            vec![
+                Field::Name,
+                Field::TradingEnd,
+                Field::DecisionPeriod,
+                Field::JudgeShare,
+                Field::JudgeCount
+            ]
+        );
+    }
+    #[test]
+    fn rejects_decision_periods_past_u32() {
+        let max_hours = (u32::MAX / 3600) as f64;
+        let max_days = (u32::MAX / 86400) as f64;
+        assert_eq!(
+            decision_period_sec(max_hours, false),
+            Some(1_193_046 * 3600)
+        );
+        assert_eq!(decision_period_sec(max_hours + 1.0, false), None);
+        assert_eq!(decision_period_sec(max_days, true), Some(49_710 * 86400));
+        assert_eq!(decision_period_sec(max_days + 1.0, true), None);
+        assert_eq!(decision_period_sec(f64::MAX, true), None);
+        assert_eq!(decision_period_sec(-1.0, true), Some(0));
+        let now = Utc::now();
+        assert_eq!(
+            fields(validate(&request(now), None, now, None)),
+            vec![Field::DecisionPeriod]
+        );
+    }
+    #[test]
+    fn needs_enough_distinct_judges() {
+        let now = Utc::now();
+        let too_few = NewPredictionRequest {
+            judge_count: 3,
+            ..request(now)
+        };
+        assert_eq!(check(&too_few, now, None), vec![Field::JudgeCount]);
+        let twice = NewPredictionRequest {
+            judges: vec![user(ALICE), user(ALICE)],
+            judge_count: 1,
+            ..request(now)
+        };
+        assert_eq!(check(&twice, now, None), vec![Field::Judges]);
+    }
+    #[test]
+    fn rejects_judging_own_prediction() {
+        let now = Utc::now();
+        assert_eq!(
+            check(&request(now), now, Some(user(ALICE))),
+            vec![Field::Judges]
+        );
+    }
+    #[test]
+    fn checks_added_judges() {
+        assert!(check_judge(user(ALICE), &[], None).is_ok());
+        assert!(check_judge(user(ALICE), &[user(ALICE)], None).is_err());
+        assert!(check_judge(user(ALICE), &[], Some(user(ALICE))).is_err());
+    }
+    #[test]
+    fn maps_server_errors_to_fields() {
+        assert_eq!(field_of("judge_count too high"), Some(Field::JudgeCount));
+        assert_eq!(
+            field_of("Trading end is in the past"),
+            Some(Field::TradingEnd)
+        );
+        assert_eq!(field_of("Unknown judge"), Some(Field::Judges));
+        assert_eq!(field_of("connection refused"), None);
+        assert_eq!(field_of("Prediction name is too long"), Some(Field::Name));
+    }
+    #[test]
+    fn ignores_transport_errors() {
+        assert_eq!(
+            field_of(
+                "error sending request for url (https://mercado.example/new_prediction): \
+                 dns error: Name or service not known"
+            ),
+            None
+        );
+        assert_eq!(field_of("TypeError: Failed to fetch prediction"), None);
+    }
+}
@@ -0,0 +1,179 @@
+//! Bindings to a browser WebLN provider (e.g. Alby) exposed as `window.webln`.
+//!
+//! The provider is looked up on every call, so any object implementing
+//! `enable`, `sendPayment` and `makeInvoice` can be assigned to `window.webln`
+//! to stub out the wallet when testing.
+use js_sys::{Array, Function, Object, Promise, Reflect};
+use leptos::window;
+use mercado::api::{Invoice, Sats};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+fn provider() -> Option<Object> {
+    let provider = Reflect::get(&window(), &JsValue::from_str("webln")).ok()?;
+    if provider.is_undefined() || provider.is_null() {
+        None
+    } else {
+        provider.dyn_into::<Object>().ok()
+    }
+}
+pub fn is_available() -> bool {
+    provider().is_some()
+}
+fn map_js_err(error: JsValue) -> String {
+    error
+        .as_string()
+        .or_else(|| {
+            Reflect::get(&error, &JsValue::from_str("message"))
+                .ok()
+                .and_then(|message| message.as_string())
+        })
+        .unwrap_or_else(|| format!("{:?}", error))
+}
+async fn invoke(provider: &Object, method: &str, args: &[JsValue]) -> Result<JsValue, String> {
+    let function = Reflect::get(provider, &JsValue::from_str(method))
+        .map_err(map_js_err)?
+        .dyn_into::<Function>()
+        .map_err(|_| format!("WebLN provider doesn't support {}", method))?;
+    let result = function
+        .apply(provider, &args.iter().collect::<Array>())
+        .map_err(map_js_err)?;
+    JsFuture::from(Promise::resolve(&result))
+        .await
+        .map_err(map_js_err)
+}
+async fn call(method: &str, args: &[JsValue]) -> Result<JsValue, String> {
+    let provider = provider().ok_or("No WebLN provider found".to_string())?;
+    invoke(&provider, "enable", &[]).await?;
+    invoke(&provider, method, args).await
+}
+fn get_string(object: &JsValue, key: &str) -> Result<String, String> {
+    Reflect::get(object, &JsValue::from_str(key))
+        .map_err(map_js_err)?
+        .as_string()
+        .ok_or(format!("WebLN response is missing {}", key))
+}
+
+/// Pays the invoice and returns the preimage.
+pub async fn send_payment(invoice: Invoice) -> Result<String, String> {
+    let response = call("sendPayment", &[JsValue::from_str(invoice.as_str())]).await?;
+    get_string(&response, "preimage")
+}
+/// Lets the provider create an invoice over `amount` that we can withdraw to.
+pub async fn make_invoice(amount: Sats) -> Result<Invoice, String> {
+    let args = Object::new();
+    Reflect::set(
+        &args,
+        &JsValue::from_str("amount"),
+        &JsValue::from_f64(amount as f64),
+    )
+    .map_err(map_js_err)?;
+    Reflect::set(
+        &args,
+        &JsValue::from_str("defaultMemo"),
+        &JsValue::from_str("Mercado withdrawal"),
+    )
+    .map_err(map_js_err)?;
+    let response = call("makeInvoice", &[args.into()]).await?;
+    get_string(&response, "paymentRequest")
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn install(methods: &[(&str, &str, &str)]) {
+        let stub = Object::new();
+        for (method, argument, body) in methods {
+            Reflect::set(
+                &stub,
+                &JsValue::from_str(method),
+                &Function::new_with_args(argument, body),
+            )
+            .unwrap();
+        }
+        Reflect::set(&window(), &JsValue::from_str("webln"), &stub).unwrap();
+    }
+    fn uninstall() {
+        Reflect::set(&window(), &JsValue::from_str("webln"), &JsValue::UNDEFINED).unwrap();
+    }
+    fn wallet() {
+        install(&[
+            ("enable", "", "return Promise.resolve()"),
+            (
+                "sendPayment",
+                "invoice",
+                "return Promise.resolve({preimage: 'preimage-of-' + invoice})",
+            ),
+            (
+                "makeInvoice",
+                "args",
+                "return Promise.resolve({paymentRequest: 'lnbc' + args.amount})",
+            ),
+        ]);
+    }
+
+    #[wasm_bindgen_test]
+    fn detects_provider() {
+        uninstall();
+        assert!(!is_available());
+        wallet();
+        assert!(is_available());
+        uninstall();
+    }
+
+    #[wasm_bindgen_test]
+    async fn pays_invoice() {
+        wallet();
+        let preimage = send_payment("lnbc1".to_string()).await;
+        uninstall();
+        assert_eq!(preimage, Ok("preimage-of-lnbc1".to_string()));
+    }
+
+    #[wasm_bindgen_test]
+    async fn makes_invoice_over_amount() {
+        wallet();
+        let invoice = make_invoice(2100).await;
+        uninstall();
+        assert_eq!(invoice, Ok("lnbc2100".to_string()));
+    }
+
+    #[wasm_bindgen_test]
+    async fn reports_missing_provider_and_rejections() {
+        uninstall();
+        assert!(send_payment("lnbc1".to_string()).await.is_err());
+        install(&[
+            ("enable", "", "return Promise.resolve()"),
+            (
+                "sendPayment",
+                "invoice",
+                "return Promise.reject(new Error('user rejected'))",
+            ),
+        ]);
+        let rejected = send_payment("lnbc1".to_string()).await;
+        let unsupported = make_invoice(1).await;
+        uninstall();
+        assert_eq!(rejected, Err("user rejected".to_string()));
+        assert_eq!(
+            unsupported,
+            Err("WebLN provider doesn't support makeInvoice".to_string())
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn rejects_malformed_response() {
+        install(&[
+            ("enable", "", "return Promise.resolve()"),
+            ("sendPayment", "invoice", "return Promise.resolve({})"),
+        ]);
+        let preimage = send_payment("lnbc1".to_string()).await;
+        uninstall();
+        assert_eq!(
+            preimage,
+            Err("WebLN response is missing preimage".to_string())
+        );
+    }
+}